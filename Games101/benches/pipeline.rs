@@ -0,0 +1,110 @@
+// 光栅化管线的性能基准: cargo bench
+// 固定使用 task3 的 spot 场景 (700x700, 默认模型变换与相机), 结果以 triangles/s 与 fragments/s 报告
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use nalgebra::Vector3;
+use games101::coverage;
+use games101::rasterizer3::{compute_barycentric2d, inside_triangle, Buffer, Rasterizer};
+use games101::task3::{setup, T3Options};
+use games101::texture::Texture;
+use games101::triangle::Triangle;
+
+struct Fixture {
+    r: Rasterizer,
+    triangles: Vec<Triangle>,
+    screen: Vec<(usize, Triangle, Vec<Vector3<f64>>)>,
+    fragments: u64, // 所有屏幕空间三角形覆盖的像素数 (深度测试前)
+}
+
+fn fixture(method: &str) -> Fixture {
+    let (mut r, mesh, camera, model, _) = setup(method, &T3Options::default()).unwrap();
+    let triangles = mesh.triangles;
+    r.set_model(model.matrix());
+    r.set_view(camera.view_matrix());
+    r.set_projection(camera.projection_matrix());
+    let screen = r.screen_triangles(&triangles).unwrap();
+    let fragments = screen.iter().map(|(_, t, _)| coverage(t, r.width(), r.height()).len() as u64).sum();
+    Fixture { r, triangles, screen, fragments }
+}
+
+// 三角形包围盒内被覆盖的像素中心
+fn coverage(t: &Triangle, width: u64, height: u64) -> Vec<(f64, f64)> {
+    coverage::coverage(&t.v, width as usize, height as usize)
+        .map(|(x, y, _)| (x as f64 + 0.5, y as f64 + 0.5))
+        .collect()
+}
+
+// 面积最大的屏幕空间三角形, 及其包围盒内的全部像素中心
+fn largest_triangle(f: &Fixture) -> (Triangle, Vec<(f64, f64)>) {
+    let (_, t, _) = f.screen.iter()
+        .max_by_key(|(_, t, _)| coverage(t, f.r.width(), f.r.height()).len())
+        .unwrap();
+    let v = &t.v;
+    let (x0, x1) = (v.iter().map(|v| v.x).fold(f64::MAX, f64::min), v.iter().map(|v| v.x).fold(f64::MIN, f64::max));
+    let (y0, y1) = (v.iter().map(|v| v.y).fold(f64::MAX, f64::min), v.iter().map(|v| v.y).fold(f64::MIN, f64::max));
+    let mut samples = vec![];
+    for y in y0.floor() as i64..=y1.ceil() as i64 {
+        for x in x0.floor() as i64..=x1.ceil() as i64 {
+            samples.push((x as f64 + 0.5, y as f64 + 0.5));
+        }
+    }
+    (t.clone(), samples)
+}
+
+fn bench_helpers(c: &mut Criterion) {
+    let f = fixture("normal");
+    let (t, samples) = largest_triangle(&f);
+    let mut group = c.benchmark_group("helpers");
+    group.throughput(Throughput::Elements(samples.len() as u64));
+    group.bench_function("inside_triangle", |b| b.iter(|| {
+        samples.iter().filter(|(x, y)| inside_triangle(black_box(*x), black_box(*y), &t.v)).count()
+    }));
+    group.bench_function("compute_barycentric2d", |b| b.iter(|| {
+        samples.iter().map(|(x, y)| compute_barycentric2d(black_box(*x), black_box(*y), &t.v).0).sum::<f64>()
+    }));
+    group.finish();
+}
+
+fn bench_texture(c: &mut Criterion) {
+    let tex = Texture::new("./models/spot/spot_texture.png").unwrap();
+    // 64x64 的均匀 uv 网格
+    let uvs: Vec<(f64, f64)> = (0..64 * 64).map(|i| ((i % 64) as f64 / 63.0, (i / 64) as f64 / 63.0)).collect();
+    let mut group = c.benchmark_group("texture");
+    group.throughput(Throughput::Elements(uvs.len() as u64));
+    group.bench_function("get_color", |b| b.iter(|| {
+        uvs.iter().map(|(u, v)| tex.get_color(black_box(*u), black_box(*v))).sum::<Vector3<f64>>()
+    }));
+    group.bench_function("get_color_bilinear", |b| b.iter(|| {
+        uvs.iter().map(|(u, v)| tex.get_color_bilinear(black_box(*u), black_box(*v))).sum::<Vector3<f64>>()
+    }));
+    group.finish();
+}
+
+// rasterize_triangle 与整帧 draw 分别按三角形数和片元数报告吞吐量
+fn bench_pipeline(c: &mut Criterion) {
+    for method in ["normal", "texture"] {
+        let mut f = fixture(method);
+        for (unit, elements) in [("triangles", f.screen.len() as u64), ("fragments", f.fragments)] {
+            let mut group = c.benchmark_group(format!("{}/{}", unit, method));
+            group.throughput(Throughput::Elements(elements));
+            group.bench_function("rasterize_triangle", |b| b.iter(|| {
+                f.r.clear(Buffer::Both);
+                for (_, t, view_pos) in &f.screen {
+                    f.r.rasterize_triangle(t, view_pos);
+                }
+            }));
+            group.bench_function("draw", |b| b.iter(|| {
+                f.r.clear(Buffer::Both);
+                f.r.draw(&f.triangles).unwrap();
+            }));
+            group.finish();
+        }
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = bench_helpers, bench_texture, bench_pipeline
+}
+criterion_main!(benches);
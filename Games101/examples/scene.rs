@@ -0,0 +1,50 @@
+// 场景图示例: 母牛与背上的小牛组成层级, 旋转母牛时小牛随之移动; 光源与相机也作为节点挂在场景中
+// cargo run --example scene -- [输出文件] [旋转角度], 需在 Games101 目录下运行
+
+use std::env;
+use std::rc::Rc;
+use games101::color::OutputTransform;
+use games101::scene::Material;
+use games101::shader::Light;
+use games101::task3::MODEL;
+use games101::utils::{normal_fragment_shader, vertex_shader};
+use games101::{image_io, Buffer, Camera, Mesh, Node, Rasterizer, Result, Scene, Transform, V3f};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let filename = args.get(1).map(String::as_str).unwrap_or("scene.png");
+    let angle: f64 = args.get(2).and_then(|a| a.parse().ok()).unwrap_or(140.0);
+
+    let spot = Rc::new(Mesh::load(MODEL)?);
+    let mut scene = Scene::new();
+    let cow = scene.add(Node::new("cow")
+                            .with_transform(Transform::new().uniform_scale(2.5).rotate_y(angle))
+                            .with_mesh(spot.clone()), None)?;
+    // 小牛的变换相对母牛: 缩小后放在背上
+    scene.add(Node::new("calf")
+                  .with_transform(Transform::new().uniform_scale(0.4).translate(V3f::new(0.0, 0.75, 0.0)))
+                  .with_mesh(spot)
+                  .with_material(Material { tint: V3f::new(1.0, 0.6, 0.6), material_id: None }), Some(cow))?;
+    scene.add(Node::new("light").with_light(Light {
+        position: V3f::new(20.0, 20.0, 20.0),
+        intensity: V3f::repeat(500.0),
+    }), None)?;
+    scene.add(Node::new("camera").with_camera(Camera::new(V3f::new(0.0, 0.0, 10.0), V3f::zeros())), None)?;
+    scene.update();
+
+    let bounds = scene.bounds(cow)?;
+    println!("cow 子树包围盒: {:?} ~ {:?}", bounds.min, bounds.max);
+
+    let mut r = Rasterizer::new(700, 700);
+    r.set_vertex_shader(vertex_shader);
+    r.set_fragment_shader(normal_fragment_shader);
+    r.clear(Buffer::Both);
+    let camera = scene.camera().unwrap();
+    let stats = scene.draw(&mut r, &camera)?;
+    println!("{}", stats);
+
+    let frame = OutputTransform::default().apply(r.frame_buffer(), 700);
+    image_io::save_frame(filename, &frame, 700, 700)?;
+    println!("已保存到 {}", filename);
+    Ok(())
+}
@@ -0,0 +1,15 @@
+// 任务1的离线版本: 渲染一帧三角形线框并保存
+// cargo run --example task1 -- [输出文件] [调试模式]
+
+use std::env;
+use games101::camera::ViewSettings;
+use games101::{task1, Result, Transform};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let filename = args.get(1).map(String::as_str).unwrap_or("task1.png");
+    let method = args.get(2).map(String::as_str).unwrap_or("");
+    task1::save(filename, method, &Transform::new(), &ViewSettings::default())?;
+    println!("已保存到 {}", filename);
+    Ok(())
+}
@@ -0,0 +1,16 @@
+// 任务3的离线版本: 用指定的 shader 渲染 spot 模型并保存, 需在 Games101 目录下运行
+// cargo run --example task3 -- [输出文件] [normal|phong|texture|bump|displacement]
+
+use std::env;
+use games101::task3::{self, T3Options};
+use games101::{image_io, Result};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let filename = args.get(1).map(String::as_str).unwrap_or("task3.png");
+    let method = args.get(2).map(String::as_str).unwrap_or("normal");
+    let frame = task3::render(method, &T3Options::default())?;
+    image_io::save_frame(filename, &frame, 700, 700)?;
+    println!("已保存到 {}", filename);
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! 转台动画: 每帧调用一次 Rasterizer::draw, 输出编号的 PNG 序列, 可选合成 GIF
+
+use std::fs::File;
+use nalgebra::{Rotation3, Vector3};
+use std::io;
+use crate::camera::Camera;
+use crate::color::OutputTransform;
+use crate::rasterizer3::{Buffer, Rasterizer};
+use crate::shader::{default_lights, Light};
+use crate::transform::Transform;
+use crate::mesh::Mesh;
+use crate::error::{Error, Result};
+use crate::image_io;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationTarget {
+    Model,  // 模型绕 y 轴旋转
+    Camera, // 相机绕目标环绕
+    Light,  // 光源绕 y 轴旋转
+}
+
+impl AnimationTarget {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "model" => Some(AnimationTarget::Model),
+            "camera" => Some(AnimationTarget::Camera),
+            "light" => Some(AnimationTarget::Light),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub target: AnimationTarget,
+    pub frames: usize,
+    pub degrees: f64,        // 整段动画转过的角度
+    pub gif: Option<String>, // GIF 输出路径
+    pub delay: u16,          // GIF 每帧时长, 单位 1/100 秒
+}
+
+impl Animation {
+    pub fn new(target: AnimationTarget, frames: usize) -> Self {
+        Animation {
+            target,
+            frames,
+            degrees: 360.0,
+            gif: None,
+            delay: 4,
+        }
+    }
+}
+
+/// 由 output.png 得到 output_0000.png, output_0001.png, ...
+pub fn frame_name(filename: &str, index: usize) -> String {
+    match filename.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}_{:04}.{}", stem, index, ext),
+        None => format!("{}_{:04}.png", filename, index),
+    }
+}
+
+pub fn render(r: &mut Rasterizer, mesh: &Mesh, camera: Camera, model: &Transform,
+              output: &OutputTransform, anim: &Animation, filename: &str) -> Result<()> {
+    let (width, height) = (r.width() as usize, r.height() as usize);
+    let mut gif_frames = vec![];
+    let lights = default_lights();
+
+    for i in 0..anim.frames {
+        let angle = anim.degrees * i as f64 / anim.frames as f64;
+        let mut camera = camera;
+        let mut model = model.clone();
+        match anim.target {
+            AnimationTarget::Model => model = model.rotate_y(angle),
+            AnimationTarget::Camera => camera.orbit(angle, 0.0),
+            AnimationTarget::Light => r.set_lights(rotate_lights(&lights, angle)),
+        }
+
+        r.clear(Buffer::Both);
+        r.set_model(model.matrix());
+        r.set_view(camera.view_matrix());
+        r.set_projection(camera.projection_matrix());
+        r.draw_mesh(mesh)?;
+
+        let frame = output.apply(r.frame_buffer(), width);
+        let image = image_io::encode(&frame, width, height);
+        image_io::save(&frame_name(filename, i), &image)?;
+        if anim.gif.is_some() {
+            gif_frames.push(image.data);
+        }
+        println!("frame {}/{}", i + 1, anim.frames);
+    }
+
+    if let Some(path) = &anim.gif {
+        write_gif(path, width, height, &gif_frames, anim.delay)?;
+    }
+    Ok(())
+}
+
+fn rotate_lights(lights: &Vec<Light>, angle: f64) -> Vec<Light> {
+    let rot = Rotation3::from_axis_angle(&Vector3::y_axis(), angle.to_radians());
+    lights.iter().map(|l| Light { position: rot * l.position, ..*l }).collect()
+}
+
+/// frames 中每帧为 RGB8, 行优先, 首行在上
+pub fn write_gif(path: &str, width: usize, height: usize, frames: &Vec<Vec<u8>>, delay: u16) -> Result<()> {
+    let to_err = |e: gif::EncodingError| match e {
+        gif::EncodingError::Io(e) => Error::io(path, e),
+        e => Error::io(path, io::Error::other(e.to_string())),
+    };
+    let file = File::create(path).map_err(|e| Error::io(path, e))?;
+    let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[]).map_err(to_err)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(to_err)?;
+    for rgb in frames {
+        let mut frame = gif::Frame::from_rgb_speed(width as u16, height as u16, rgb, 10);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(to_err)?;
+    }
+    Ok(())
+}
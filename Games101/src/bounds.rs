@@ -0,0 +1,194 @@
+//! 包围体: 轴对齐包围盒与包围球, 以及由 MVP 矩阵得到的视锥, 用于场景图中物体的范围与视锥剔除
+
+use nalgebra::{Vector3, Vector4};
+use crate::triangle::Triangle;
+use crate::utils::{M4f, V3f};
+
+/// 轴对齐包围盒; 不包含任何点时 min > max
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: V3f,
+    pub max: V3f,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::empty()
+    }
+}
+
+impl Aabb {
+    pub fn empty() -> Self {
+        Aabb { min: Vector3::repeat(f64::MAX), max: Vector3::repeat(f64::MIN) }
+    }
+
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a V3f>) -> Self {
+        let mut aabb = Aabb::empty();
+        points.into_iter().for_each(|p| aabb.extend(p));
+        aabb
+    }
+
+    /// 三角形顶点的包围盒 (顶点的 w 为 1)
+    pub fn from_triangles(triangles: &[Triangle]) -> Self {
+        let mut aabb = Aabb::empty();
+        for v in triangles.iter().flat_map(|t| t.v.iter()) {
+            aabb.extend(&v.xyz());
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn extend(&mut self, p: &V3f) {
+        self.min = self.min.inf(p);
+        self.max = self.max.sup(p);
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb { min: self.min.inf(&other.min), max: self.max.sup(&other.max) }
+    }
+
+    pub fn center(&self) -> V3f {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> V3f {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [V3f; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vector3::new(a.x, a.y, a.z), Vector3::new(b.x, a.y, a.z),
+            Vector3::new(a.x, b.y, a.z), Vector3::new(b.x, b.y, a.z),
+            Vector3::new(a.x, a.y, b.z), Vector3::new(b.x, a.y, b.z),
+            Vector3::new(a.x, b.y, b.z), Vector3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    /// 变换后八个角点的包围盒 (仿射变换), 可能比变换前的盒子更松
+    pub fn transform(&self, m: &M4f) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        let corners = self.corners().map(|c| (m * Vector4::new(c.x, c.y, c.z, 1.0)).xyz());
+        Aabb::from_points(&corners)
+    }
+}
+
+/// 包围球
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sphere {
+    pub center: V3f,
+    pub radius: f64,
+}
+
+impl Sphere {
+    /// 以包围盒中心为球心, 包含所有点的球 (不是最小包围球)
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a V3f> + Clone) -> Self {
+        let center = Aabb::from_points(points.clone()).center();
+        let radius = points.into_iter().map(|p| (p - center).norm()).fold(0.0, f64::max);
+        Sphere { center, radius }
+    }
+
+    pub fn from_triangles(triangles: &[Triangle]) -> Self {
+        let points: Vec<V3f> = triangles.iter().flat_map(|t| t.v.iter().map(|v| v.xyz())).collect();
+        Sphere::from_points(&points)
+    }
+}
+
+/// 视锥的六个平面 (a, b, c, d), 点 p 在平面内侧当且仅当 a*x + b*y + c*z + d >= 0
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [Vector4<f64>; 6],
+}
+
+impl Frustum {
+    /// 由 MVP 矩阵提取平面 (Gribb-Hartmann), 平面位于 model space, 因此可以直接检验局部空间的包围体
+    pub fn from_matrix(mvp: &M4f) -> Self {
+        let row = |i: usize| mvp.row(i).transpose();
+        let planes = [
+            row(3) + row(0), row(3) - row(0),
+            row(3) + row(1), row(3) - row(1),
+            row(3) + row(2), row(3) - row(2),
+        ].map(|p| p / p.xyz().norm().max(1e-12));
+        Frustum { planes }
+    }
+
+    fn distance(plane: &Vector4<f64>, p: &V3f) -> f64 {
+        plane.xyz().dot(p) + plane.w
+    }
+
+    /// 包围盒是否可能与视锥相交; 只在确定位于某个平面外侧时返回 false
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        !aabb.is_empty() && self.planes.iter().all(|plane| {
+            // 沿平面法线方向最远的角点
+            let p = Vector3::new(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            Self::distance(plane, &p) >= 0.0
+        })
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.planes.iter().all(|plane| Self::distance(plane, &sphere.center) >= -sphere.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+
+    // 相机在 (0, 0, 5) 看向原点, 竖直视角 45 度; z = 0 处视锥的半宽约为 2.07
+    fn frustum() -> Frustum {
+        let camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zeros());
+        Frustum::from_matrix(&(camera.projection_matrix() * camera.view_matrix()))
+    }
+
+    fn aabb(min: (f64, f64, f64), max: (f64, f64, f64)) -> Aabb {
+        Aabb { min: Vector3::new(min.0, min.1, min.2), max: Vector3::new(max.0, max.1, max.2) }
+    }
+
+    fn sphere(aabb: &Aabb) -> Sphere {
+        Sphere::from_points(&aabb.corners())
+    }
+
+    #[test]
+    fn box_inside() {
+        let b = aabb((-1.0, -1.0, -1.0), (1.0, 1.0, 1.0));
+        assert!(frustum().intersects_aabb(&b));
+        assert!(frustum().intersects_sphere(&sphere(&b)));
+    }
+
+    #[test]
+    fn box_straddling_a_plane() {
+        let b = aabb((1.5, -0.5, -0.5), (4.0, 0.5, 0.5));
+        assert!(frustum().intersects_aabb(&b));
+        assert!(frustum().intersects_sphere(&sphere(&b)));
+    }
+
+    #[test]
+    fn box_outside() {
+        let b = aabb((10.0, -1.0, -1.0), (12.0, 1.0, 1.0));
+        assert!(!frustum().intersects_aabb(&b));
+        assert!(!frustum().intersects_sphere(&sphere(&b)));
+    }
+
+    #[test]
+    fn box_behind_camera() {
+        // 正对视线方向, 但在相机背后
+        let b = aabb((-1.0, -1.0, 7.0), (1.0, 1.0, 9.0));
+        assert!(!frustum().intersects_aabb(&b));
+        assert!(!frustum().intersects_sphere(&sphere(&b)));
+    }
+
+    #[test]
+    fn empty_box_is_culled() {
+        assert!(!frustum().intersects_aabb(&Aabb::empty()));
+    }
+}
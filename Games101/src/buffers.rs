@@ -0,0 +1,145 @@
+//! 顶点/下标缓冲的存储: 与 frame buffer 等渲染目标分开保存, draw 时只借用, 不再复制
+//! 句柄带有代数 (generation), 缓冲删除后旧句柄失效, 即使槽位被新缓冲复用也不会误用
+
+use std::fmt;
+use std::marker::PhantomData;
+use crate::error::{Error, Result};
+
+/// 指向 Arena<T> 中某个元素的句柄
+pub struct Handle<T> {
+    index: usize,
+    generation: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+// 手动实现, 避免 derive 要求 T: Clone / Copy / PartialEq
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Clone)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// 带代数的槽位数组, 删除后的槽位会被复用
+#[derive(Clone)]
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Arena { slots: Vec::new(), free: Vec::new() }
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn insert(&mut self, value: T) -> Handle<T> {
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index].value = Some(value);
+                index
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, value: Some(value) });
+                self.slots.len() - 1
+            }
+        };
+        Handle { index, generation: self.slots[index].generation, marker: PhantomData }
+    }
+
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.slots.get(handle.index)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.slots.get_mut(handle.index)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    /// 与 get 相同, 句柄失效时返回错误
+    pub fn try_get(&self, handle: Handle<T>) -> Result<&T> {
+        self.get(handle).ok_or_else(|| stale(handle))
+    }
+
+    pub fn try_get_mut(&mut self, handle: Handle<T>) -> Result<&mut T> {
+        self.get_mut(handle).ok_or_else(|| stale(handle))
+    }
+
+    /// 删除元素并使句柄失效
+    pub fn remove(&mut self, handle: Handle<T>) -> Result<T> {
+        let slot = self.slots.get_mut(handle.index)
+            .filter(|slot| slot.generation == handle.generation && slot.value.is_some())
+            .ok_or_else(|| stale(handle))?;
+        slot.generation += 1;
+        self.free.push(handle.index);
+        Ok(slot.value.take().unwrap())
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn stale<T>(handle: Handle<T>) -> Error {
+    Error::InvalidParameter(format!("缓冲 {:?} 已被删除或不存在", handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_handle_fails_after_remove() {
+        let mut arena = Arena::default();
+        let a = arena.insert(1);
+        assert_eq!(arena.remove(a).unwrap(), 1);
+        assert!(arena.get(a).is_none());
+        assert!(arena.try_get(a).is_err());
+        assert!(arena.remove(a).is_err());
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn reused_slot_bumps_generation() {
+        let mut arena = Arena::default();
+        let a = arena.insert("a");
+        arena.remove(a).unwrap();
+        let b = arena.insert("b");
+        // 复用同一个槽位, 但旧句柄不能访问新元素
+        assert_eq!(b.index, a.index);
+        assert_eq!(b.generation, a.generation + 1);
+        assert_ne!(a, b);
+        assert!(arena.get(a).is_none());
+        assert_eq!(arena.get(b), Some(&"b"));
+        assert_eq!(arena.len(), 1);
+    }
+}
@@ -0,0 +1,199 @@
+//! 相机: look-at 视图矩阵, 透视/正交投影, 以及 orbit / pan / zoom 操作
+
+use nalgebra::{Matrix4, Rotation3, Unit, Vector3};
+use crate::error::{Error, Result};
+use crate::utils::{M4f, V3f};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub eye: V3f,
+    pub target: V3f,
+    pub up: V3f,
+    pub fov: f64,          // 竖直视角, 角度制
+    pub aspect: f64,       // 宽 / 高
+    pub near: f64,
+    pub far: f64,
+    pub projection: Projection,
+    pub ortho_height: f64, // 正交投影时视口的高度 (world space)
+}
+
+impl Camera {
+    /// 近/远平面 0.1/50 与各 rasterizer 视口变换中的 f1/f2 一致
+    pub fn new(eye: V3f, target: V3f) -> Self {
+        Camera {
+            eye,
+            target,
+            up: Vector3::new(0.0, 1.0, 0.0),
+            fov: 45.0,
+            aspect: 1.0,
+            near: 0.1,
+            far: 50.0,
+            projection: Projection::Perspective,
+            ortho_height: 2.0 * (eye - target).norm() * (22.5f64).to_radians().tan(),
+        }
+    }
+
+    /// eye 与 target 重合或 up 与视线平行时无法确定相机朝向
+    pub fn validate(&self) -> Result<()> {
+        let offset = self.target - self.eye;
+        if offset.norm() < 1e-9 {
+            return Err(Error::InvalidParameter(format!("相机位置 {:?} 与观察点重合", self.eye.as_slice())));
+        }
+        if offset.normalize().cross(&self.up).norm() < 1e-6 {
+            return Err(Error::InvalidParameter(format!("相机 up 方向 {:?} 与视线平行", self.up.as_slice())));
+        }
+        Ok(())
+    }
+
+    pub fn forward(&self) -> V3f {
+        (self.target - self.eye).normalize()
+    }
+
+    // 相机坐标系: (right, up, forward)
+    fn basis(&self) -> (V3f, V3f, V3f) {
+        let f = self.forward();
+        // 视线与 up 平行时换一个参考方向
+        let up = if f.cross(&self.up).norm() < 1e-6 { Vector3::new(0.0, 0.0, 1.0) } else { self.up };
+        let s = f.cross(&up).normalize();
+        let u = s.cross(&f);
+        (s, u, f)
+    }
+
+    pub fn view_matrix(&self) -> M4f {
+        let (s, u, f) = self.basis();
+        let e = self.eye;
+        Matrix4::new(
+            s.x, s.y, s.z, -s.dot(&e),
+            u.x, u.y, u.z, -u.dot(&e),
+            -f.x, -f.y, -f.z, f.dot(&e),
+            0.0, 0.0, 0.0, 1.0,
+        )
+    }
+
+    /// 相机看向 -z, near/far 为正值, 映射到 NDC 的 [-1, 1]
+    pub fn projection_matrix(&self) -> M4f {
+        let (n, f) = (self.near, self.far);
+        match self.projection {
+            Projection::Perspective => {
+                let t = 1.0 / (self.fov.to_radians() / 2.0).tan();
+                Matrix4::new(
+                    t / self.aspect, 0.0, 0.0, 0.0,
+                    0.0, t, 0.0, 0.0,
+                    0.0, 0.0, -(f + n) / (f - n), -2.0 * f * n / (f - n),
+                    0.0, 0.0, -1.0, 0.0,
+                )
+            }
+            Projection::Orthographic => {
+                let h = self.ortho_height / 2.0;
+                let w = h * self.aspect;
+                Matrix4::new(
+                    1.0 / w, 0.0, 0.0, 0.0,
+                    0.0, 1.0 / h, 0.0, 0.0,
+                    0.0, 0.0, -2.0 / (f - n), -(f + n) / (f - n),
+                    0.0, 0.0, 0.0, 1.0,
+                )
+            }
+        }
+    }
+
+    /// 绕 target 旋转, yaw 绕 up 轴, pitch 绕相机 right 轴 (角度制)
+    pub fn orbit(&mut self, yaw: f64, pitch: f64) {
+        let (s, _, _) = self.basis();
+        let offset = self.eye - self.target;
+        let yaw_rot = Rotation3::from_axis_angle(&Unit::new_normalize(self.up), yaw.to_radians());
+        let mut pitched = Rotation3::from_axis_angle(&Unit::new_normalize(s), pitch.to_radians()) * offset;
+        // 不允许越过头顶/脚底, 否则视线会与 up 平行或画面翻转
+        let up = self.up.normalize();
+        let horizontal = |v: &V3f| v - up * v.dot(&up);
+        if pitched.normalize().dot(&up).abs() > 0.999 || horizontal(&pitched).dot(&horizontal(&offset)) <= 0.0 {
+            pitched = offset;
+        }
+        self.eye = self.target + yaw_rot * pitched;
+    }
+
+    /// 在视平面内平移相机与 target, dx/dy 以到 target 距离为单位
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        let (s, u, _) = self.basis();
+        let dist = (self.eye - self.target).norm();
+        let delta = (s * dx + u * dy) * dist;
+        self.eye += delta;
+        self.target += delta;
+    }
+
+    /// factor < 1 拉近, > 1 拉远
+    pub fn zoom(&mut self, factor: f64) {
+        match self.projection {
+            Projection::Perspective => {
+                let offset = (self.eye - self.target) * factor;
+                if offset.norm() > self.near {
+                    self.eye = self.target + offset;
+                }
+            }
+            Projection::Orthographic => self.ortho_height *= factor,
+        }
+    }
+
+    pub fn toggle_projection(&mut self) {
+        self.projection = match self.projection {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        };
+    }
+
+    /// 交互任务中的相机按键, 返回是否处理了该按键
+    /// j/l: 水平环绕  i/k: 竖直环绕  w/s: 拉近/拉远  J/L/I/K: 平移  o: 切换透视/正交
+    pub fn handle_key(&mut self, key: i32) -> bool {
+        let key = match u8::try_from(key) {
+            Ok(k) => k as char,
+            Err(_) => return false,
+        };
+        match key {
+            'j' => self.orbit(-10.0, 0.0),
+            'l' => self.orbit(10.0, 0.0),
+            'i' => self.orbit(0.0, 10.0),
+            'k' => self.orbit(0.0, -10.0),
+            'w' => self.zoom(0.9),
+            's' => self.zoom(1.0 / 0.9),
+            'J' => self.pan(-0.05, 0.0),
+            'L' => self.pan(0.05, 0.0),
+            'I' => self.pan(0.0, 0.05),
+            'K' => self.pan(0.0, -0.05),
+            'o' => self.toggle_projection(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// 输出分辨率与相机参数, 由命令行指定; eye 为 None 时使用各任务默认的相机位置
+#[derive(Clone, Copy, Debug)]
+pub struct ViewSettings {
+    pub width: usize,
+    pub height: usize,
+    pub eye: Option<V3f>,
+    pub fov: f64, // 竖直视角, 角度制
+}
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        ViewSettings { width: 700, height: 700, eye: None, fov: 45.0 }
+    }
+}
+
+impl ViewSettings {
+    /// 看向原点的相机, 宽高比与输出分辨率一致; 相机位置不合法时返回错误
+    pub fn camera(&self, default_eye: V3f) -> Result<Camera> {
+        let mut camera = Camera::new(self.eye.unwrap_or(default_eye), Vector3::zeros());
+        camera.fov = self.fov;
+        camera.aspect = self.width as f64 / self.height as f64;
+        camera.ortho_height = 2.0 * (camera.eye - camera.target).norm() * (self.fov / 2.0).to_radians().tan();
+        camera.validate()?;
+        Ok(camera)
+    }
+}
@@ -0,0 +1,158 @@
+//! 颜色空间转换与输出变换
+//! frame buffer 中保存的是线性空间的颜色 (0~255 标度), 输出前再做编码
+
+use nalgebra::Vector3;
+use crate::utils::V3f;
+
+/// 纹理/颜色数据所处的颜色空间
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+/// 输出时使用的传递函数
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transfer {
+    Linear,
+    Srgb,
+    Gamma(f64), // 纯幂函数编码 c^(1/gamma)
+}
+
+impl Transfer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Transfer::Linear),
+            "srgb" => Some(Transfer::Srgb),
+            _ => None,
+        }
+    }
+}
+
+/// sRGB -> 线性, 输入输出均为 [0, 1]
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// 线性 -> sRGB, 输入输出均为 [0, 1]
+pub fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear_v3(c: &V3f) -> V3f {
+    Vector3::new(srgb_to_linear(c.x), srgb_to_linear(c.y), srgb_to_linear(c.z))
+}
+
+pub fn linear_to_srgb_v3(c: &V3f) -> V3f {
+    Vector3::new(linear_to_srgb(c.x), linear_to_srgb(c.y), linear_to_srgb(c.z))
+}
+
+/// 8-bit 解码查找表, 纹理加载时生成一次
+pub fn decode_lut(space: ColorSpace) -> [f64; 256] {
+    let mut lut = [0.0; 256];
+    for (i, v) in lut.iter_mut().enumerate() {
+        let c = i as f64 / 255.0;
+        *v = match space {
+            ColorSpace::Srgb => srgb_to_linear(c),
+            ColorSpace::Linear => c,
+        };
+    }
+    lut
+}
+
+/// 色调映射算子, 输入输出均为线性颜色 (1.0 为白)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMap {
+    None, // 直接截断到 [0, 1]
+    Reinhard,
+    Aces,
+    Uncharted2,
+}
+
+impl ToneMap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(ToneMap::None),
+            "reinhard" => Some(ToneMap::Reinhard),
+            "aces" => Some(ToneMap::Aces),
+            "uncharted2" => Some(ToneMap::Uncharted2),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, c: f64) -> f64 {
+        let c = c.max(0.0);
+        match self {
+            ToneMap::None => c,
+            ToneMap::Reinhard => c / (1.0 + c),
+            // Narkowicz 对 ACES RRT + ODT 的拟合
+            ToneMap::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+            // Hable 的 filmic 曲线, 曝光偏置 2.0, 白点 11.2
+            ToneMap::Uncharted2 => {
+                let hable = |x: f64| {
+                    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+                    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+                };
+                hable(2.0 * c) / hable(11.2)
+            }
+        }
+    }
+}
+
+// 4x4 Bayer 矩阵, 用于有序抖动
+const BAYER4: [[f64; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+/// frame buffer -> 8-bit 之前的输出变换: 曝光 -> 色调映射 -> 编码 -> 抖动
+#[derive(Clone, Copy, Debug)]
+pub struct OutputTransform {
+    pub exposure: f64, // 曝光补偿, 单位 EV (乘以 2^exposure)
+    pub tone_map: ToneMap,
+    pub transfer: Transfer,
+    pub dither: bool,
+}
+
+impl Default for OutputTransform {
+    fn default() -> Self {
+        OutputTransform {
+            exposure: 0.0,
+            tone_map: ToneMap::None,
+            transfer: Transfer::Srgb,
+            dither: false,
+        }
+    }
+}
+
+impl OutputTransform {
+    /// 输入为线性颜色 (0~255), 输出为编码后的颜色 (0~255), 量化交给 image_io::encode
+    pub fn apply(&self, frame_buf: &Vec<V3f>, width: usize) -> Vec<V3f> {
+        frame_buf.iter().enumerate().map(|(i, c)| {
+            let scale = 2f64.powf(self.exposure) / 255.0;
+            let mut c = c.map(|x| self.tone_map.apply(x * scale).clamp(0.0, 1.0));
+            c = match self.transfer {
+                Transfer::Linear => c,
+                Transfer::Srgb => linear_to_srgb_v3(&c),
+                Transfer::Gamma(gamma) => c.map(|x| x.powf(1.0 / gamma)),
+            };
+            c *= 255.0;
+            if self.dither {
+                let (x, y) = (i % width, i / width);
+                let offset = (BAYER4[y % 4][x % 4] + 0.5) / 16.0 - 0.5;
+                c = c.map(|v| (v + offset).clamp(0.0, 255.0));
+            }
+            c
+        }).collect()
+    }
+}
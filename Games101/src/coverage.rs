@@ -0,0 +1,37 @@
+//! 三角形覆盖: 各个 pass 共用的包围盒扫描, 以像素中心采样
+//! 顶点已位于屏幕空间 (x, y 为像素坐标), 像素 (x, y) 的中心为 (x + 0.5, y + 0.5)
+
+use nalgebra::Vector4;
+use crate::rasterizer3::{compute_barycentric2d, inside_triangle};
+
+/// 像素矩形 ((x0, x1), (y0, y1)), 两端都包含在内
+pub type Rect = ((usize, usize), (usize, usize));
+
+/// 三角形包围盒与 [0, width) x [0, height) 的交, 为空时返回 None
+pub fn bounding_rect(v: &[Vector4<f64>; 3], width: usize, height: usize) -> Option<Rect> {
+    let x0 = (v.iter().map(|v| v.x).fold(f64::MAX, f64::min).floor() as i64).max(0);
+    let x1 = (v.iter().map(|v| v.x).fold(f64::MIN, f64::max).ceil() as i64).min(width as i64 - 1);
+    let y0 = (v.iter().map(|v| v.y).fold(f64::MAX, f64::min).floor() as i64).max(0);
+    let y1 = (v.iter().map(|v| v.y).fold(f64::MIN, f64::max).ceil() as i64).min(height as i64 - 1);
+    if x0 > x1 || y0 > y1 {
+        return None;
+    }
+    Some(((x0 as usize, x1 as usize), (y0 as usize, y1 as usize)))
+}
+
+/// rect 内被三角形覆盖的像素, 逐行给出 (x, y, 重心坐标)
+pub fn pixels(v: &[Vector4<f64>; 3], ((x0, x1), (y0, y1)): Rect) -> impl Iterator<Item = (usize, usize, (f64, f64, f64))> + '_ {
+    (y0..=y1).flat_map(move |y| (x0..=x1).map(move |x| (x, y))).filter_map(move |(x, y)| {
+        let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+        if inside_triangle(px, py, v) {
+            Some((x, y, compute_barycentric2d(px, py, v)))
+        } else {
+            None
+        }
+    })
+}
+
+/// 整个 width x height 画面内被三角形覆盖的像素
+pub fn coverage(v: &[Vector4<f64>; 3], width: usize, height: usize) -> impl Iterator<Item = (usize, usize, (f64, f64, f64))> + '_ {
+    bounding_rect(v, width, height).into_iter().flat_map(move |rect| pixels(v, rect))
+}
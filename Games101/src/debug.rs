@@ -0,0 +1,186 @@
+//! 调试可视化: 与具体 rasterizer 无关, 输入为屏幕空间三角形
+//! (v.xy 为像素坐标, v.z 为屏幕深度, v.w 为裁剪空间 w)
+
+use nalgebra::{Vector2, Vector3};
+use crate::gbuffer::id_color;
+use crate::coverage::coverage;
+use crate::rasterizer3::compute_barycentric2d;
+use crate::triangle::Triangle;
+use crate::utils::V3f;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugMode {
+    Barycentric,
+    Uv,
+    Depth,
+    TriangleId,
+    Overdraw,
+    MipLevel,
+}
+
+impl DebugMode {
+    pub const NAMES: [&'static str; 6] = ["barycentric", "uv", "depth", "triangle-id", "overdraw", "mip-level"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "barycentric" => Some(DebugMode::Barycentric),
+            "uv" => Some(DebugMode::Uv),
+            "depth" => Some(DebugMode::Depth),
+            "triangle-id" => Some(DebugMode::TriangleId),
+            "overdraw" => Some(DebugMode::Overdraw),
+            "mip-level" => Some(DebugMode::MipLevel),
+            _ => None,
+        }
+    }
+}
+
+// 与 get_new_tri 中的视口变换保持一致
+const Z_NEAR: f64 = 0.1;
+const Z_FAR: f64 = 50.0;
+
+#[derive(Clone)]
+pub struct DebugBuffer {
+    pub mode: DebugMode,
+    width: usize,
+    height: usize,
+    depth: Vec<f64>,
+    barycentric: Vec<V3f>,
+    uv: Vec<Vector2<f64>>,
+    uv_footprint: Vec<f64>, // 一个像素在 uv 空间中的跨度
+    triangle: Vec<usize>,
+    overdraw: Vec<u32>,
+}
+
+impl DebugBuffer {
+    pub fn new(mode: DebugMode, width: usize, height: usize) -> Self {
+        let n = width * height;
+        DebugBuffer {
+            mode,
+            width,
+            height,
+            depth: vec![f64::MAX; n],
+            barycentric: vec![Vector3::zeros(); n],
+            uv: vec![Vector2::zeros(); n],
+            uv_footprint: vec![0.0; n],
+            triangle: vec![0; n],
+            overdraw: vec![0; n],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.depth.fill(f64::MAX);
+        self.overdraw.fill(0);
+    }
+
+    pub fn rasterize(&mut self, t: &Triangle, id: usize) {
+        let v = &t.v;
+        for (x, y, (a, b, c)) in coverage(v, self.width, self.height) {
+            let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+            let ind = (self.height - 1 - y) * self.width + x;
+            self.overdraw[ind] += 1;
+
+            let z = a * v[0].z + b * v[1].z + c * v[2].z;
+            if z >= self.depth[ind] {
+                continue;
+            }
+            self.depth[ind] = z;
+            self.barycentric[ind] = Vector3::new(a, b, c);
+            self.triangle[ind] = id;
+            let uv = Self::perspective_uv(t, px, py);
+            self.uv[ind] = uv;
+            let du = Self::perspective_uv(t, px + 1.0, py) - uv;
+            let dv = Self::perspective_uv(t, px, py + 1.0) - uv;
+            self.uv_footprint[ind] = du.norm().max(dv.norm());
+        }
+    }
+
+    fn perspective_uv(t: &Triangle, x: f64, y: f64) -> Vector2<f64> {
+        let (a, b, c) = compute_barycentric2d(x, y, &t.v);
+        let (a, b, c) = (a / t.v[0].w, b / t.v[1].w, c / t.v[2].w);
+        (a * t.tex_coords[0] + b * t.tex_coords[1] + c * t.tex_coords[2]) / (a + b + c)
+    }
+
+    fn covered(&self, ind: usize) -> bool {
+        self.depth[ind] < f64::MAX
+    }
+
+    // 把屏幕深度还原为 view space 线性深度
+    fn linear_depth(z: f64) -> f64 {
+        let f1 = (Z_FAR - Z_NEAR) / 2.0;
+        let f2 = (Z_FAR + Z_NEAR) / 2.0;
+        let ndc = (z - f2) / f1;
+        2.0 * Z_NEAR * Z_FAR / (Z_FAR + Z_NEAR - ndc * (Z_FAR - Z_NEAR))
+    }
+
+    /// 生成可直接显示的颜色 (0~255), texture_size 用于计算 mip 层级
+    pub fn resolve(&self, texture_size: Option<(usize, usize)>) -> Vec<V3f> {
+        let n = self.width * self.height;
+        let (mut d_min, mut d_max, mut max_overdraw) = (f64::MAX, f64::MIN, 1);
+        for i in 0..n {
+            if self.covered(i) {
+                let d = Self::linear_depth(self.depth[i]);
+                d_min = d_min.min(d);
+                d_max = d_max.max(d);
+            }
+            max_overdraw = max_overdraw.max(self.overdraw[i]);
+        }
+        let (tw, th) = texture_size.unwrap_or((1024, 1024));
+
+        (0..n).map(|i| {
+            if self.mode == DebugMode::Overdraw {
+                return heatmap(self.overdraw[i] as f64 / max_overdraw as f64) * 255.0;
+            }
+            if !self.covered(i) {
+                return Vector3::zeros();
+            }
+            let color = match self.mode {
+                DebugMode::Barycentric => self.barycentric[i],
+                DebugMode::Uv => Vector3::new(self.uv[i].x, self.uv[i].y, 0.0),
+                DebugMode::Depth => {
+                    let d = Self::linear_depth(self.depth[i]);
+                    Vector3::repeat(1.0 - (d - d_min) / (d_max - d_min).max(1e-9))
+                }
+                DebugMode::TriangleId => id_color(self.triangle[i]),
+                DebugMode::MipLevel => {
+                    let texels = self.uv_footprint[i] * tw.max(th) as f64;
+                    mip_color(texels.max(1e-9).log2().max(0.0))
+                }
+                DebugMode::Overdraw => unreachable!(),
+            };
+            color.map(|x| x.clamp(0.0, 1.0)) * 255.0
+        }).collect()
+    }
+}
+
+// 0 为黑, 随后 蓝 -> 青 -> 绿 -> 黄 -> 红
+fn heatmap(t: f64) -> V3f {
+    if t <= 0.0 {
+        return Vector3::zeros();
+    }
+    let stops = [
+        Vector3::new(0.0, 0.0, 1.0),
+        Vector3::new(0.0, 1.0, 1.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        Vector3::new(1.0, 1.0, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+    ];
+    let s = t.min(1.0) * (stops.len() - 1) as f64;
+    let i = (s as usize).min(stops.len() - 2);
+    stops[i].lerp(&stops[i + 1], s - i as f64)
+}
+
+// 每个 mip 层级一种颜色, 层级之间线性过渡
+fn mip_color(level: f64) -> V3f {
+    let palette = [
+        Vector3::new(0.0, 0.0, 1.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        Vector3::new(1.0, 1.0, 0.0),
+        Vector3::new(1.0, 0.5, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(1.0, 0.0, 1.0),
+        Vector3::new(1.0, 1.0, 1.0),
+    ];
+    let i = (level as usize).min(palette.len() - 1);
+    let j = (i + 1).min(palette.len() - 1);
+    palette[i].lerp(&palette[j], level - level.floor())
+}
@@ -0,0 +1,61 @@
+//! crate 统一的错误类型, 各层通过 ? 一路传回 main, 由 main 打印并以非零状态退出
+
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    // 读写文件失败
+    Io { path: String, source: io::Error },
+    // 文件能打开但内容无法解析 (图像、obj 等)
+    Decode { path: String, message: String },
+    // 网格缺少所需数据, 或三角形数据不合法
+    InvalidGeometry(String),
+    // 调用参数或命令行参数不合法
+    InvalidParameter(String),
+    #[cfg(feature = "opencv")]
+    OpenCv(opencv::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: &str, source: io::Error) -> Self {
+        Error::Io { path: path.to_owned(), source }
+    }
+
+    pub fn decode(path: &str, message: impl fmt::Display) -> Self {
+        Error::Decode { path: path.to_owned(), message: message.to_string() }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "无法读写 {}: {}", path, source),
+            Error::Decode { path, message } => write!(f, "无法解析 {}: {}", path, message),
+            Error::InvalidGeometry(message) => write!(f, "几何数据错误: {}", message),
+            Error::InvalidParameter(message) => write!(f, "参数错误: {}", message),
+            #[cfg(feature = "opencv")]
+            Error::OpenCv(e) => write!(f, "OpenCV 错误: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            #[cfg(feature = "opencv")]
+            Error::OpenCv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "opencv")]
+impl From<opencv::Error> for Error {
+    fn from(e: opencv::Error) -> Self {
+        Error::OpenCv(e)
+    }
+}
@@ -0,0 +1,91 @@
+//! 延迟渲染使用的 G-buffer, 像素排布与 frame buffer 相同
+
+use nalgebra::{Vector2, Vector3};
+use crate::error::Result;
+use crate::image_io::save_frame;
+use crate::utils::V3f;
+
+// 导出的一个通道: 文件名后缀与由像素下标得到 0~1 颜色的函数
+type Channel<'a> = (&'static str, Box<dyn Fn(usize) -> V3f + 'a>);
+
+pub struct GBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub position: Vec<V3f>, // view space
+    pub normal: Vec<V3f>,   // view space, 未归一化
+    pub tex_coords: Vec<Vector2<f64>>,
+    pub albedo: Vec<V3f>,   // 0~1, sRGB
+    pub material: Vec<usize>,
+    pub instance: Vec<usize>,
+    pub depth: Vec<f64>,    // 屏幕空间深度, f64::MAX 表示未覆盖
+}
+
+impl GBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let n = width * height;
+        GBuffer {
+            width,
+            height,
+            position: vec![Vector3::zeros(); n],
+            normal: vec![Vector3::zeros(); n],
+            tex_coords: vec![Vector2::zeros(); n],
+            albedo: vec![Vector3::zeros(); n],
+            material: vec![0; n],
+            instance: vec![0; n],
+            depth: vec![f64::MAX; n],
+        }
+    }
+
+    pub fn covered(&self, ind: usize) -> bool {
+        self.depth[ind] < f64::MAX
+    }
+
+    /// 把各通道导出为 {prefix}_{通道}.png
+    pub fn export(&self, prefix: &str) -> Result<()> {
+        let (pos_min, pos_max) = self.bounds(&self.position);
+        let pos_range = (pos_max - pos_min).map(|x| x.max(1e-9));
+        let (d_min, d_max) = self.bounds(&self.position.iter().map(|p| Vector3::repeat(-p.z)).collect::<Vec<_>>());
+        let d_range = (d_max.x - d_min.x).max(1e-9);
+
+        let channels: Vec<Channel> = vec![
+            ("position", Box::new(|i| (self.position[i] - pos_min).component_div(&pos_range))),
+            ("normal", Box::new(|i| (self.normal[i].normalize() + Vector3::repeat(1.0)) / 2.0)),
+            ("uv", Box::new(|i| Vector3::new(self.tex_coords[i].x, self.tex_coords[i].y, 0.0))),
+            ("albedo", Box::new(|i| self.albedo[i])),
+            ("material", Box::new(|i| id_color(self.material[i]))),
+            ("instance", Box::new(|i| id_color(self.instance[i]))),
+            ("depth", Box::new(|i| Vector3::repeat(1.0 - (-self.position[i].z - d_min.x) / d_range))),
+        ];
+        for (name, f) in channels {
+            let buf: Vec<V3f> = (0..self.width * self.height)
+                .map(|i| if self.covered(i) { f(i).map(|x| x.clamp(0.0, 1.0)) * 255.0 } else { Vector3::zeros() })
+                .collect();
+            save_frame(&format!("{}_{}.png", prefix, name), &buf, self.width, self.height)?;
+        }
+        Ok(())
+    }
+
+    // 已覆盖像素的逐分量最小/最大值
+    fn bounds(&self, data: &[V3f]) -> (V3f, V3f) {
+        let mut lo = Vector3::repeat(f64::MAX);
+        let mut hi = Vector3::repeat(f64::MIN);
+        for (i, v) in data.iter().enumerate() {
+            if self.covered(i) {
+                lo = lo.inf(v);
+                hi = hi.sup(v);
+            }
+        }
+        (lo, hi)
+    }
+}
+
+/// 由 id 得到一个固定的伪随机颜色
+pub fn id_color(id: usize) -> V3f {
+    let mut h = (id as u64).wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15);
+    h ^= h >> 29;
+    Vector3::new(
+        (h & 0xff) as f64 / 255.0,
+        ((h >> 8) & 0xff) as f64 / 255.0,
+        ((h >> 16) & 0xff) as f64 / 255.0,
+    )
+}
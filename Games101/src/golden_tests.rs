@@ -0,0 +1,157 @@
+// 渲染回归测试: 离线渲染固定场景, 与 tests/golden 下的参考图像比较
+// 比较失败时在 target/golden-diff 下写出实际结果与差异图
+// 有意修改渲染结果后, 用 GOLDEN_BLESS=1 cargo test 更新参考图像
+// 前向的 rasterize_triangle 与光照着色器留作练习, 输出全黑, 所以 spot 走延迟渲染, 另用调试模式覆盖深度和纹理坐标
+
+use std::env;
+use std::fs;
+use nalgebra::Vector3;
+use crate::camera::ViewSettings;
+use crate::image_io::{self, RgbImage};
+use crate::rasterizer3::Buffer;
+use crate::task3::T3Options;
+use crate::transform::Transform;
+use crate::utils::V3f;
+use crate::{task1, task2, task3};
+
+const GOLDEN_DIR: &str = "tests/golden";
+const DIFF_DIR: &str = "target/golden-diff";
+const MIN_PSNR: f64 = 40.0;
+const MIN_SSIM: f64 = 0.99;
+const SIZE: usize = 700;
+
+fn psnr(a: &RgbImage, b: &RgbImage) -> f64 {
+    let mse = a.data.iter().zip(&b.data)
+        .map(|(x, y)| (*x as f64 - *y as f64).powi(2))
+        .sum::<f64>() / a.data.len() as f64;
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+}
+
+fn luminance(img: &RgbImage) -> Vec<f64> {
+    img.data.chunks(3).map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64).collect()
+}
+
+// 在 8x8 不重叠窗口上计算亮度通道的 SSIM 并取平均
+fn ssim(a: &RgbImage, b: &RgbImage) -> f64 {
+    let (la, lb) = (luminance(a), luminance(b));
+    let (c1, c2) = ((0.01f64 * 255.0).powi(2), (0.03f64 * 255.0).powi(2));
+    let (mut total, mut windows) = (0.0, 0);
+    for wy in (0..a.height - 7).step_by(8) {
+        for wx in (0..a.width - 7).step_by(8) {
+            let idx: Vec<usize> = (0..64).map(|i| (wy + i / 8) * a.width + wx + i % 8).collect();
+            let mean = |l: &Vec<f64>| idx.iter().map(|&i| l[i]).sum::<f64>() / 64.0;
+            let (ma, mb) = (mean(&la), mean(&lb));
+            let (mut va, mut vb, mut cov) = (0.0, 0.0, 0.0);
+            for &i in &idx {
+                va += (la[i] - ma).powi(2);
+                vb += (lb[i] - mb).powi(2);
+                cov += (la[i] - ma) * (lb[i] - mb);
+            }
+            let (va, vb, cov) = (va / 63.0, vb / 63.0, cov / 63.0);
+            total += ((2.0 * ma * mb + c1) * (2.0 * cov + c2)) / ((ma * ma + mb * mb + c1) * (va + vb + c2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
+
+// 差异放大 4 倍, 便于肉眼查看
+fn write_diff(name: &str, actual: &RgbImage, expected: &RgbImage) {
+    fs::create_dir_all(DIFF_DIR).unwrap();
+    image_io::save(&format!("{}/{}_actual.png", DIFF_DIR, name), actual).unwrap();
+    let diff: Vec<V3f> = actual.data.chunks(3).zip(expected.data.chunks(3))
+        .map(|(p, q)| {
+            let d = |i: usize| ((p[i] as f64 - q[i] as f64).abs() * 4.0).min(255.0);
+            Vector3::new(d(0), d(1), d(2))
+        })
+        .collect();
+    image_io::save_frame(&format!("{}/{}_diff.png", DIFF_DIR, name), &diff, SIZE, SIZE).unwrap();
+}
+
+fn check(name: &str, frame: Vec<V3f>) {
+    let path = format!("{}/{}.png", GOLDEN_DIR, name);
+    assert!(frame.iter().any(|c| c.max() > 0.0), "{}: nothing was rendered", name);
+    let actual = image_io::encode(&frame, SIZE, SIZE);
+    if env::var_os("GOLDEN_BLESS").is_some() {
+        // 记录当前结果, 需要提交到仓库中
+        fs::create_dir_all(GOLDEN_DIR).unwrap();
+        image_io::save(&path, &actual).unwrap();
+        eprintln!("recorded golden image {}", path);
+        return;
+    }
+    let expected = image_io::load(&path).unwrap_or_else(|e| {
+        panic!("{}: cannot load golden image ({}), run GOLDEN_BLESS=1 cargo test to record it", name, e)
+    });
+    assert_eq!((actual.width, actual.height), (expected.width, expected.height), "{}: size mismatch", name);
+
+    let (p, s) = (psnr(&actual, &expected), ssim(&actual, &expected));
+    if p < MIN_PSNR || s < MIN_SSIM {
+        write_diff(name, &actual, &expected);
+        panic!("{}: PSNR {:.2} dB (min {}), SSIM {:.4} (min {}), see {}/{}_diff.png",
+               name, p, MIN_PSNR, s, MIN_SSIM, DIFF_DIR, name);
+    }
+}
+
+#[test]
+fn task1_wireframe() {
+    check("task1_wireframe", task1::render("normal", Transform::new().matrix(), &ViewSettings::default()).unwrap());
+}
+
+#[test]
+fn task1_rotated() {
+    check("task1_rotated", task1::render("normal", Transform::new().rotate_z(30.0).matrix(), &ViewSettings::default()).unwrap());
+}
+
+#[test]
+fn task2_triangle_id() {
+    check("task2_triangle_id", task2::render("triangle-id", Transform::new().matrix(), &ViewSettings::default()).unwrap());
+}
+
+fn spot(method: &str) {
+    let opts = T3Options { deferred: true, ..T3Options::default() };
+    check(&format!("spot_{}", method.replace('-', "_")), task3::render(method, &opts).unwrap());
+}
+
+#[test]
+fn spot_normal() {
+    spot("normal");
+}
+
+#[test]
+fn spot_depth() {
+    spot("depth");
+}
+
+#[test]
+fn spot_uv() {
+    spot("uv");
+}
+
+#[test]
+fn spot_mip_level() {
+    spot("mip-level");
+}
+
+// 前向的 rasterize_triangle 留作练习, 以同样只着色可见片元的延迟渲染作为关闭 early-Z 时的参照
+#[test]
+fn early_z_matches_depth_tested_shading() {
+    let render = |early_z: bool| {
+        let opts = T3Options { early_z, deferred: !early_z, instances: 4, ..T3Options::default() };
+        let (mut r, mesh, camera, model, _) = task3::setup("normal", &opts).unwrap();
+        r.clear(Buffer::Both);
+        r.set_view(camera.view_matrix());
+        r.set_projection(camera.projection_matrix());
+        let stats = task3::draw(&mut r, &mesh, &model, &opts).unwrap();
+        (r.frame_buffer().clone(), stats)
+    };
+    let (on, stats) = render(true);
+    let (off, _) = render(false);
+    assert!(stats.triangles_occluded > 0 && stats.tiles_occluded > 0, "{}", stats);
+    assert!(on.iter().any(|c| c.max() > 0.0), "nothing was shaded");
+    let max_diff = on.iter().zip(&off).map(|(a, b)| (a - b).abs().max()).fold(0.0, f64::max);
+    assert!(max_diff < 1e-9, "early-Z differs from the reference by {}", max_diff);
+}
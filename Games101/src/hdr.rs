@@ -0,0 +1,109 @@
+//! 浮点 / HDR 导出: 不经过 8 位量化, 直接写出 frame buffer 或深度缓冲的原始数值
+//! 根据扩展名选择格式: .exr (OpenEXR), .pfm (Portable Float Map), .hdr (Radiance RGBE)
+//! 颜色从 0~255 标度换算为线性 1.0 = 白; 深度为屏幕空间 z, 未覆盖的像素为 +inf
+//! 两种缓冲的布局都与 frame buffer 相同, 下标 0 为左上角
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use exr::prelude::{Image, SpecificChannels, Vec2, WritableImage};
+use crate::error::{Error, Result};
+use crate::utils::V3f;
+
+fn from_exr(path: &str, e: exr::error::Error) -> Error {
+    match e {
+        exr::error::Error::Io(e) => Error::io(path, e),
+        e => Error::io(path, io::Error::other(e.to_string())),
+    }
+}
+
+fn unsupported(ext: &str) -> Error {
+    Error::InvalidParameter(format!("不支持的 HDR 扩展名 `{}`, 可用 exr / pfm / hdr", ext))
+}
+
+fn extension(path: &str) -> String {
+    Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase()
+}
+
+/// 只检查扩展名, 以便在渲染之前就报告不支持的格式
+pub fn check_extension(path: &str) -> Result<()> {
+    match extension(path).as_str() {
+        "exr" | "pfm" | "hdr" => Ok(()),
+        ext => Err(unsupported(ext)),
+    }
+}
+
+pub fn write_color(path: &str, frame_buf: &Vec<V3f>, width: usize, height: usize) -> Result<()> {
+    let pixels: Vec<[f32; 3]> = frame_buf.iter()
+        .map(|c| [(c.x / 255.0) as f32, (c.y / 255.0) as f32, (c.z / 255.0) as f32])
+        .collect();
+    match extension(path).as_str() {
+        "exr" => exr::prelude::write_rgb_file(path, width, height, |x, y| {
+            let [r, g, b] = pixels[y * width + x];
+            (r, g, b)
+        }).map_err(|e| from_exr(path, e)),
+        "pfm" => write_pfm(path, &pixels, width, height),
+        "hdr" => write_radiance(path, &pixels, width, height),
+        ext => Err(unsupported(ext)),
+    }
+}
+
+/// Radiance HDR 只有 RGB 格式, 深度写入三个通道
+pub fn write_depth(path: &str, depth_buf: &Vec<f64>, width: usize, height: usize) -> Result<()> {
+    let depth: Vec<f32> = depth_buf.iter()
+        .map(|&z| if z < f64::MAX { z as f32 } else { f32::INFINITY })
+        .collect();
+    match extension(path).as_str() {
+        "exr" => {
+            let channels = SpecificChannels::build()
+                .with_channel("Z")
+                .with_pixel_fn(|Vec2(x, y)| (depth[y * width + x],));
+            Image::from_channels((width, height), channels)
+                .write().to_file(path)
+                .map_err(|e| from_exr(path, e))
+        }
+        "pfm" => write_pfm(path, &depth.iter().map(|&z| [z]).collect::<Vec<_>>(), width, height),
+        "hdr" => write_radiance(path, &depth.iter().map(|&z| [z; 3]).collect::<Vec<_>>(), width, height),
+        ext => Err(unsupported(ext)),
+    }
+}
+
+// PFM: "PF" 为 RGB, "Pf" 为单通道; 负的比例因子表示小端; 行从下往上存储
+fn write_pfm<const N: usize>(path: &str, pixels: &Vec<[f32; N]>, width: usize, height: usize) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path).map_err(|e| Error::io(path, e))?);
+    let magic = if N == 3 { "PF" } else { "Pf" };
+    let mut data = format!("{}\n{} {}\n-1.0\n", magic, width, height).into_bytes();
+    for row in pixels.chunks(width).rev() {
+        for value in row.iter().flatten() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    out.write_all(&data).and_then(|_| out.flush()).map_err(|e| Error::io(path, e))
+}
+
+// Radiance RGBE, 不使用行程编码; "-Y h +X w" 表示行从上往下存储
+fn write_radiance(path: &str, pixels: &Vec<[f32; 3]>, width: usize, height: usize) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path).map_err(|e| Error::io(path, e))?);
+    let mut data = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width).into_bytes();
+    for p in pixels {
+        data.extend_from_slice(&rgbe(p));
+    }
+    out.write_all(&data).and_then(|_| out.flush()).map_err(|e| Error::io(path, e))
+}
+
+// 三个分量共享一个指数: 最大分量 m = f * 2^e, f 属于 [0.5, 1)
+fn rgbe(c: &[f32; 3]) -> [u8; 4] {
+    let c = c.map(|x| if x.is_finite() { x.max(0.0) } else { f32::MAX });
+    let m = c[0].max(c[1]).max(c[2]);
+    if m < 1e-32 {
+        return [0; 4];
+    }
+    let e = m.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f32.powi(e);
+    [
+        (c[0] * scale).min(255.0) as u8,
+        (c[1] * scale).min(255.0) as u8,
+        (c[2] * scale).min(255.0) as u8,
+        (e + 128).clamp(0, 255) as u8,
+    ]
+}
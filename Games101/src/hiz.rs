@@ -0,0 +1,132 @@
+//! 分层深度 (Hi-Z): 每个 TILE_SIZE x TILE_SIZE 的块记录其中最远的深度,
+//! 三角形或块的最近深度不小于它时一定无法通过深度测试, 可以整体跳过
+//! 深度只会变小 (除了 clear), 因此过期的块深度只会偏大, 剔除结果总是保守的
+
+use crate::coverage::Rect;
+
+/// 块的边长 (像素)
+pub const TILE_SIZE: usize = 8;
+
+pub struct HiZ {
+    pub tiles_x: usize,
+    pub tiles_y: usize,
+    width: usize,
+    height: usize,
+    max_depth: Vec<f64>, // 按块行优先, y 向上
+}
+
+impl HiZ {
+    pub fn new(width: usize, height: usize) -> Self {
+        let (tiles_x, tiles_y) = (width.div_ceil(TILE_SIZE), height.div_ceil(TILE_SIZE));
+        HiZ { tiles_x, tiles_y, width, height, max_depth: vec![f64::MAX; tiles_x * tiles_y] }
+    }
+
+    pub fn clear(&mut self) {
+        self.max_depth.fill(f64::MAX);
+    }
+
+    /// 像素 (x, y) 所在的块
+    pub fn tile_of(x: usize, y: usize) -> (usize, usize) {
+        (x / TILE_SIZE, y / TILE_SIZE)
+    }
+
+    /// 块 (tx, ty) 覆盖的像素范围 (含两端)
+    pub fn tile_pixels(&self, tx: usize, ty: usize) -> Rect {
+        let (x0, y0) = (tx * TILE_SIZE, ty * TILE_SIZE);
+        ((x0, (x0 + TILE_SIZE).min(self.width) - 1), (y0, (y0 + TILE_SIZE).min(self.height) - 1))
+    }
+
+    /// 最近深度为 z_min 的图元在块 (tx, ty) 中是否一定被遮挡
+    pub fn occluded(&self, tx: usize, ty: usize, z_min: f64) -> bool {
+        z_min >= self.max_depth[ty * self.tiles_x + tx]
+    }
+
+    /// 覆盖像素矩形 rect 的所有块是否都遮挡了最近深度为 z_min 的图元
+    pub fn occluded_rect(&self, ((x0, x1), (y0, y1)): Rect, z_min: f64) -> bool {
+        let ((tx0, ty0), (tx1, ty1)) = (Self::tile_of(x0, y0), Self::tile_of(x1, y1));
+        (ty0..=ty1).all(|ty| (tx0..=tx1).all(|tx| self.occluded(tx, ty, z_min)))
+    }
+
+    /// 由深度缓冲重新计算块 (tx, ty) 的最远深度; depth 的排布与 frame buffer 相同 (首行在上)
+    pub fn update(&mut self, depth: &[f64], tx: usize, ty: usize) {
+        let ((x0, x1), (y0, y1)) = self.tile_pixels(tx, ty);
+        let mut max = f64::MIN;
+        for y in y0..=y1 {
+            let row = (self.height - 1 - y) * self.width;
+            max = depth[row + x0..=row + x1].iter().fold(max, |m, &d| m.max(d));
+        }
+        self.max_depth[ty * self.tiles_x + tx] = max;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 12x10 的画面分为 2x2 块, 右侧一列块宽 4, 上方一行块高 2
+    const W: usize = 12;
+    const H: usize = 10;
+
+    fn depth(f: impl Fn(usize, usize) -> f64) -> Vec<f64> {
+        let mut depth = vec![0.0; W * H];
+        for y in 0..H {
+            for x in 0..W {
+                depth[(H - 1 - y) * W + x] = f(x, y);
+            }
+        }
+        depth
+    }
+
+    fn update_all(hiz: &mut HiZ, depth: &[f64]) {
+        for ty in 0..hiz.tiles_y {
+            for tx in 0..hiz.tiles_x {
+                hiz.update(depth, tx, ty);
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_is_occluded_after_clear() {
+        let mut hiz = HiZ::new(W, H);
+        assert_eq!((hiz.tiles_x, hiz.tiles_y), (2, 2));
+        assert!(!hiz.occluded_rect(((0, W - 1), (0, H - 1)), 1e9));
+        update_all(&mut hiz, &depth(|_, _| 0.5));
+        assert!(hiz.occluded(0, 0, 0.5));
+        hiz.clear();
+        assert!(!hiz.occluded(0, 0, 0.5));
+    }
+
+    #[test]
+    fn update_keeps_farthest_depth_of_tile() {
+        let mut hiz = HiZ::new(W, H);
+        let depth = depth(|x, y| if (x, y) == (7, 0) { 0.7 } else if x < 8 && y < 8 { 0.5 } else { f64::MAX });
+        hiz.update(&depth, 0, 0);
+        assert!(hiz.occluded(0, 0, 0.7));
+        assert!(!hiz.occluded(0, 0, 0.69));
+        // 其他块没有更新
+        assert!(!hiz.occluded(1, 0, 0.7));
+    }
+
+    #[test]
+    fn partial_edge_tile() {
+        let mut hiz = HiZ::new(W, H);
+        assert_eq!(hiz.tile_pixels(1, 1), ((8, 11), (8, 9)));
+        // 只有画面内的像素参与计算, 块外的部分不会越界也不会影响结果
+        let depth = depth(|x, y| if x >= 8 && y >= 8 { 0.3 } else { 0.9 });
+        hiz.update(&depth, 1, 1);
+        assert!(hiz.occluded(1, 1, 0.3));
+        assert!(!hiz.occluded(1, 1, 0.29));
+        assert!(hiz.occluded_rect(((9, 11), (8, 9)), 0.4));
+    }
+
+    #[test]
+    fn occluded_rect_needs_every_tile() {
+        let mut hiz = HiZ::new(W, H);
+        // 右下块仍为空, 其余块的最远深度为 0.5
+        update_all(&mut hiz, &depth(|x, y| if x >= 8 && y < 8 { f64::MAX } else { 0.5 }));
+        assert!(hiz.occluded_rect(((0, 7), (0, 9)), 0.6));
+        assert!(hiz.occluded_rect(((2, 11), (8, 9)), 0.6));
+        assert!(!hiz.occluded_rect(((2, 11), (8, 9)), 0.4));
+        assert!(!hiz.occluded_rect(((6, 9), (6, 9)), 0.6));
+    }
+}
@@ -0,0 +1,86 @@
+//! 图像读写后端: 默认使用 OpenCV; 开启 `image` feature 后改用纯 Rust 的 image crate,
+//! 此时可以用 --no-default-features 在没有 OpenCV 的环境下构建 (交互窗口除外)
+
+#[cfg(not(feature = "image"))]
+use std::io;
+use crate::error::{Error, Result};
+use crate::utils::V3f;
+
+#[cfg(not(any(feature = "image", feature = "opencv")))]
+compile_error!("至少需要开启 `image` 或 `opencv` feature 之一作为图像读写后端");
+
+/// 8-bit RGB, 行优先, 首行在上
+#[derive(Clone, Debug)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl RgbImage {
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * self.width + x) * 3;
+        [self.data[i], self.data[i + 1], self.data[i + 2]]
+    }
+}
+
+/// 把已编码的颜色 (0~255) 量化为 8 位; 与原先 convert_to(CV_8UC3, 1.0, 1.0) 的结果一致 (beta = 1)
+pub fn encode(frame_buffer: &Vec<V3f>, width: usize, height: usize) -> RgbImage {
+    let quantize = |x: f64| (x + 1.0).round().clamp(0.0, 255.0) as u8;
+    let data = frame_buffer.iter().flat_map(|c| [quantize(c.x), quantize(c.y), quantize(c.z)]).collect();
+    RgbImage { width, height, data }
+}
+
+pub fn save_frame(path: &str, frame_buffer: &Vec<V3f>, width: usize, height: usize) -> Result<()> {
+    save(path, &encode(frame_buffer, width, height))
+}
+
+#[cfg(feature = "image")]
+pub fn load(path: &str) -> Result<RgbImage> {
+    let img = image::open(path).map_err(|e| from_image(path, e))?.to_rgb8();
+    Ok(RgbImage { width: img.width() as usize, height: img.height() as usize, data: img.into_raw() })
+}
+
+#[cfg(feature = "image")]
+pub fn save(path: &str, img: &RgbImage) -> Result<()> {
+    image::save_buffer(path, &img.data, img.width as u32, img.height as u32, image::ColorType::Rgb8)
+        .map_err(|e| from_image(path, e))
+}
+
+#[cfg(feature = "image")]
+fn from_image(path: &str, e: image::ImageError) -> Error {
+    match e {
+        image::ImageError::IoError(e) => Error::io(path, e),
+        e => Error::decode(path, e),
+    }
+}
+
+#[cfg(not(feature = "image"))]
+pub fn load(path: &str) -> Result<RgbImage> {
+    use opencv::core::MatTraitConst;
+    use opencv::imgcodecs::{imread, IMREAD_COLOR};
+    let mat = imread(path, IMREAD_COLOR)?;
+    if mat.cols() == 0 || mat.rows() == 0 {
+        return Err(Error::decode(path, "文件不存在或格式不受支持"));
+    }
+    let (width, height) = (mat.cols() as usize, mat.rows() as usize);
+    let data = mat.data_bytes()?.chunks(3).flat_map(|p| [p[2], p[1], p[0]]).collect();
+    Ok(RgbImage { width, height, data })
+}
+
+#[cfg(not(feature = "image"))]
+pub fn save(path: &str, img: &RgbImage) -> Result<()> {
+    if !opencv::imgcodecs::imwrite(path, &to_mat(img)?, &opencv::core::Vector::new())? {
+        return Err(Error::io(path, io::Error::other("imwrite 失败")));
+    }
+    Ok(())
+}
+
+/// 交互窗口使用的 BGR Mat
+#[cfg(feature = "opencv")]
+pub fn to_mat(img: &RgbImage) -> Result<opencv::core::Mat> {
+    use opencv::core::{Mat, MatTraitConst};
+    let bgr: Vec<u8> = img.data.chunks(3).flat_map(|p| [p[2], p[1], p[0]]).collect();
+    let mat = Mat::from_slice(&bgr)?.reshape(3, img.height as i32)?.try_clone()?;
+    Ok(mat)
+}
@@ -0,0 +1,136 @@
+//! 实例化绘制: 同一网格按多组模型矩阵与属性绘制多次, 着色器通过实例编号区分
+
+use nalgebra::Vector3;
+use crate::gbuffer::id_color;
+use crate::transform::Transform;
+use crate::utils::{M4f, V3f};
+
+/// 一个实例的属性
+#[derive(Clone, Copy, Debug)]
+pub struct Instance {
+    pub model: M4f,
+    pub tint: V3f,                  // 与漫反射系数逐分量相乘, (1, 1, 1) 为不变
+    pub material_id: Option<usize>, // 代替三角形自带的材质编号
+}
+
+impl Instance {
+    pub fn new(model: M4f) -> Self {
+        Instance { model, tint: Vector3::repeat(1.0), material_id: None }
+    }
+
+    pub fn with_tint(mut self, tint: V3f) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_material(mut self, material_id: usize) -> Self {
+        self.material_id = Some(material_id);
+        self
+    }
+}
+
+/// 在 xz 平面上以原点为中心排成近似正方形的网格, 相邻实例间距为 spacing
+/// 每个实例先做 model 变换再平移到格点, 颜色取 id_color 与白色的平均, 避免过暗
+pub fn grid(count: usize, spacing: f64, model: &Transform) -> Vec<Instance> {
+    let columns = (count as f64).sqrt().ceil().max(1.0) as usize;
+    let rows = count.div_ceil(columns);
+    let offset = |n: usize, i: usize| (i as f64 - (n - 1) as f64 / 2.0) * spacing;
+    (0..count).map(|i| {
+        let (row, column) = (i / columns, i % columns);
+        let position = Vector3::new(offset(columns, column), 0.0, offset(rows, row));
+        Instance::new(model.clone().translate(position).matrix()).with_tint((id_color(i) + Vector3::repeat(1.0)) / 2.0)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use nalgebra::{Matrix4, Vector4};
+    use super::*;
+    use crate::rasterizer3::{Buffer, Rasterizer};
+    use crate::shader::{FragmentShaderPayload, VertexShaderPayload};
+    use crate::triangle::Triangle;
+
+    thread_local! {
+        static VERTICES: RefCell<Vec<(usize, V3f, usize)>> = const { RefCell::new(vec![]) };
+    }
+
+    // 记录顶点阶段看到的实例属性, 并按实例编号把三角形左右分开
+    fn vertex_shader(payload: &VertexShaderPayload) -> V3f {
+        VERTICES.with(|v| v.borrow_mut().push((payload.instance_id, payload.tint, payload.material_id)));
+        payload.position + Vector3::new(payload.instance_id as f64 - 0.5, 0.0, 0.0)
+    }
+
+    // 把片元阶段看到的实例属性写进颜色
+    fn fragment_shader(payload: &FragmentShaderPayload) -> V3f {
+        Vector3::new(payload.instance_id as f64, payload.material_id as f64, payload.tint.x)
+    }
+
+    fn triangle() -> Triangle {
+        let mut t = Triangle::new();
+        t.set_vertex(0, Vector4::new(-0.4, -0.4, 0.0, 1.0));
+        t.set_vertex(1, Vector4::new(0.4, -0.4, 0.0, 1.0));
+        t.set_vertex(2, Vector4::new(0.0, 0.4, 0.0, 1.0));
+        t.material_id = 3;
+        t
+    }
+
+    // view 与 projection 为单位矩阵, 顶点坐标即 NDC
+    fn rasterizer(size: u64) -> Rasterizer {
+        let mut r = Rasterizer::new(size, size);
+        r.set_view(Matrix4::identity());
+        r.set_projection(Matrix4::identity());
+        r.clear(Buffer::Both);
+        r
+    }
+
+    #[test]
+    fn instance_attributes_reach_both_stages() {
+        let instances = [
+            Instance::new(Matrix4::identity()),
+            Instance::new(Matrix4::identity()).with_tint(Vector3::new(0.5, 0.25, 0.125)).with_material(7),
+        ];
+
+        let size = 20;
+        let mut r = rasterizer(size);
+        r.set_deferred(true);
+        r.set_vertex_shader(vertex_shader);
+        r.set_fragment_shader(fragment_shader);
+        r.draw_instanced(&vec![triangle()], &instances).unwrap();
+
+        let vertices = VERTICES.with(|v| v.take());
+        assert_eq!(vertices.len(), 6);
+        for (k, instance) in instances.iter().enumerate() {
+            let seen: Vec<_> = vertices.iter().filter(|v| v.0 == k).collect();
+            assert_eq!(seen.len(), 3, "instance {}", k);
+            let material = instance.material_id.unwrap_or(3);
+            assert!(seen.iter().all(|v| v.1 == instance.tint && v.2 == material), "instance {}: {:?}", k, seen);
+        }
+
+        // 顶点着色器把实例 0 移到左半边, 实例 1 移到右半边
+        let mut covered = [0; 2];
+        for (ind, c) in r.frame_buffer().iter().enumerate() {
+            if r.depth_buffer()[ind] == f64::MAX {
+                continue;
+            }
+            let k = (ind % size as usize >= size as usize / 2) as usize;
+            let expected = Vector3::new(k as f64, instances[k].material_id.unwrap_or(3) as f64, instances[k].tint.x);
+            assert_eq!(*c, expected, "pixel {}", ind);
+            covered[k] += 1;
+        }
+        assert!(covered[0] > 0 && covered[1] > 0, "{:?}", covered);
+    }
+
+    #[test]
+    fn singular_instance_model_is_rejected() {
+        let mut r = rasterizer(20);
+        r.set_deferred(true);
+        r.set_fragment_shader(fragment_shader);
+        let flat = Transform::new().scale(Vector3::new(1.0, 1.0, 0.0)).matrix();
+        let instances = [Instance::new(Matrix4::identity()), Instance::new(flat)];
+        assert!(r.draw_instanced(&vec![triangle()], &instances).is_err());
+        // 出错时什么都不画
+        assert!(r.depth_buffer().iter().all(|&z| z == f64::MAX));
+        assert!(r.draw_instanced(&vec![triangle()], &instances[..1]).is_ok());
+    }
+}
@@ -0,0 +1,85 @@
+//! Games101 软光栅器
+//!
+//! 三个任务各自的 rasterizer 以及它们共用的网格、纹理、着色器和数学工具都以库的形式提供,
+//! `src/main.rs` 与 `examples/` 下的各任务只是在此之上的薄封装.
+//!
+//! - [`rasterizer1`] / [`rasterizer2`]: 任务1的线框与任务2的纯色三角形, 通过位置/下标/颜色缓冲绘制
+//! - [`rasterizer3`]: 带着色器的完整流水线, 直接接收 [`Triangle`] 列表; [`instance`] 用于实例化绘制
+//! - [`triangle`], [`utils::load_triangles`], [`mesh`]: 网格数据、obj 加载与分簇的包围体
+//! - [`texture`], [`shader`], [`utils`]: 纹理采样、着色器输入与各任务的着色器
+//! - [`camera`], [`transform`], [`color`]: 相机、模型变换与输出变换
+//! - [`scene`], [`bounds`]: 层级变换的场景图与包围盒, 由遍历发出 draw
+//! - [`image_io`], [`hdr`]: 8 位图像与浮点图像的读写
+//!
+//! 在自己的程序中使用 rasterizer3 绘制一个三角形:
+//!
+//! ```
+//! use nalgebra::Vector4;
+//! use games101::{Buffer, Camera, Rasterizer, Transform, Triangle, V3f};
+//! use games101::utils::{normal_fragment_shader, vertex_shader};
+//!
+//! let mut t = Triangle::new();
+//! t.v = [Vector4::new(-1.0, -1.0, 0.0, 1.0), Vector4::new(1.0, -1.0, 0.0, 1.0), Vector4::new(0.0, 1.0, 0.0, 1.0)];
+//! t.normal = [V3f::z(); 3];
+//!
+//! let camera = Camera::new(V3f::new(0.0, 0.0, 5.0), V3f::zeros());
+//! let mut r = Rasterizer::new(64, 64);
+//! r.set_vertex_shader(vertex_shader);
+//! r.set_fragment_shader(normal_fragment_shader);
+//! r.clear(Buffer::Both);
+//! r.set_model(Transform::new().matrix());
+//! r.set_view(camera.view_matrix());
+//! r.set_projection(camera.projection_matrix());
+//! let stats = r.draw(&vec![t]).unwrap();
+//! assert_eq!(stats.triangles_submitted, 1);
+//! assert_eq!(r.frame_buffer().len(), 64 * 64);
+//! ```
+
+pub mod triangle;
+pub mod buffers;
+pub mod topology;
+pub mod rasterizer1;
+pub mod rasterizer2;
+pub mod rasterizer3;
+pub mod instance;
+pub mod bounds;
+pub mod mesh;
+pub mod hiz;
+pub mod scene;
+pub mod utils;
+pub mod texture;
+pub mod shader;
+pub mod color;
+pub mod shadow;
+pub mod coverage;
+pub mod gbuffer;
+pub mod ssao;
+pub mod debug;
+pub mod stats;
+pub mod error;
+pub mod hdr;
+pub mod image_io;
+pub mod camera;
+pub mod transform;
+pub mod animation;
+#[cfg(feature = "opencv")]
+pub mod viewer;
+#[cfg(test)]
+mod golden_tests;
+
+pub mod task1;
+pub mod task2;
+pub mod task3;
+
+pub use camera::Camera;
+pub use error::{Error, Result};
+pub use instance::Instance;
+pub use rasterizer3::{Buffer, Rasterizer};
+pub use mesh::Mesh;
+pub use scene::{Node, Scene};
+pub use shader::{FragmentShaderPayload, Light, VertexShaderPayload};
+pub use stats::RenderStats;
+pub use texture::Texture;
+pub use transform::Transform;
+pub use triangle::Triangle;
+pub use utils::{load_triangles, M4f, V3f};
@@ -1,66 +1,366 @@
 // #![allow(warnings)]
 
-mod triangle;
-pub mod rasterizer1;
-pub mod rasterizer2;
-pub mod rasterizer3;
-mod utils;
-mod texture;
-mod shader;
+use std::process::ExitCode;
+use clap::builder::PossibleValuesParser;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use games101::camera::ViewSettings;
+use games101::color::{OutputTransform, ToneMap, Transfer};
+use games101::debug::DebugMode;
+use games101::shadow::{ShadowFilter, ShadowSettings};
+use games101::ssao::SsaoSettings;
+use games101::transform::Transform;
+use games101::animation::{Animation, AnimationTarget};
+use games101::error::{Error, Result};
+use games101::utils::{V3f, SHADERS};
+#[cfg(feature = "opencv")]
+use games101::viewer;
+use games101::{task1, task2};
+use games101::task3::{t3, T3Options};
 
-extern crate opencv;
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-use std::env;
-use clap::{App, Arg};  
-use std::io;  
-use nalgebra::Vector3;
-use opencv::core::Vector;
-use utils::*;
-use crate::shader::FragmentShaderPayload;
-use crate::texture::Texture;
+fn cli() -> Command<'static> {
+    let mesh_methods: Vec<&str> = SHADERS.iter().chain(DebugMode::NAMES.iter()).copied().collect();
+    Command::new("games101")
+        .about("Games101 软光栅器")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("wireframe")
+                .about("任务1: 三角形线框")
+                .args(common_args("绕z轴旋转的角度"))
+                .arg(method_arg(DebugMode::NAMES.to_vec(), None))
+        )
+        .subcommand(
+            Command::new("triangles")
+                .about("任务2: 三个相互遮挡的纯色三角形")
+                .args(common_args("绕z轴旋转的角度"))
+                .arg(method_arg(DebugMode::NAMES.to_vec(), None))
+        )
+        .subcommand(
+            Command::new("mesh")
+                .about("任务3: 带着色器渲染obj模型, 默认为spot")
+                .args(common_args("绕y轴旋转的角度, 默认140 (未指定--transform时)"))
+                .arg(method_arg(mesh_methods, Some("normal")))
+                .args(mesh_args())
+        )
+}
 
-mod task1;
-mod task2;
-mod task3;
-use task1::t1;
-use task2::t2;
-use task3::t3;
+// 三个任务共用的参数: 输出文件、分辨率、相机与模型变换
+fn common_args(angle_help: &'static str) -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("output")
+            .short('o')
+            .long("output")
+            .value_name("FILE")
+            .help("输出文件名")
+            .default_value("output.png"),
+        Arg::new("width")
+            .long("width")
+            .value_name("PIXELS")
+            .help("输出宽度")
+            .default_value("700")
+            .value_parser(value_parser!(u64).range(1..)),
+        Arg::new("height")
+            .long("height")
+            .value_name("PIXELS")
+            .help("输出高度")
+            .default_value("700")
+            .value_parser(value_parser!(u64).range(1..)),
+        Arg::new("eye")
+            .long("eye")
+            .value_name("X,Y,Z")
+            .help("相机位置, 相机始终看向原点; 默认任务1/2为0,0,5, 任务3为0,0,10")
+            .allow_hyphen_values(true)
+            .value_parser(parse_vec3),
+        Arg::new("fov")
+            .long("fov")
+            .value_name("DEGREES")
+            .help("竖直视角")
+            .default_value("45")
+            .value_parser(|s: &str| match s.parse::<f64>() {
+                Ok(fov) if fov > 0.0 && fov < 180.0 => Ok(fov),
+                Ok(_) => Err(String::from("视角应在 (0, 180) 之间")),
+                Err(e) => Err(e.to_string()),
+            }),
+        Arg::new("angle")
+            .long("angle")
+            .value_name("DEGREES")
+            .help(angle_help)
+            .allow_hyphen_values(true)
+            .value_parser(value_parser!(f64)),
+        Arg::new("transform")
+            .long("transform")
+            .value_name("SPEC")
+            .help("模型变换, 按书写顺序作用, 例: \"s=2.5;r=0,1,0,140;t=0,0,-1\" (t平移 s缩放 r轴角 q四元数 e欧拉角)")
+            .value_parser(|s: &str| s.parse::<Transform>()),
+        Arg::new("interactive")
+            .long("interactive")
+            .help("打开交互窗口 (需要opencv feature), 相机按键: j/l/i/k环绕, w/s缩放, J/L/I/K平移, o切换投影")
+            .action(ArgAction::SetTrue),
+    ]
+}
 
-fn main(){
-    // 定义命令行参数  
-    let matches = App::new("选择任务")    
-        .arg(  
-            Arg::with_name("任务序号")  
-                .short('i')  
-                .long("index")  
-                .help("任务序号")  
-                .required(true)  // 设置为必需参数  
-                .takes_value(true)  
-                .validator(|s| s.parse::<u32>().map_err(|e| e.to_string())),  // 验证参数是否为u32类型  
-        )  
-        .arg(  
-            Arg::with_name("输出文件名")  
-                .short('n')  
-                .long("name")  
-                .help("输出文件名")  
-                .takes_value(true)  
-        )
-        .arg(  
-            Arg::with_name("渲染方式")  
-                .short('m')  
-                .long("method")  
-                .help("渲染方式")  
-                .takes_value(true)  
-        )  
-        .get_matches();
-    let count: u32 = matches.value_of("任务序号").unwrap_or("1").parse().unwrap();  // 如果参数缺失或无法解析，程序会panic 
-    let filename = String::from(matches.value_of("输出文件名").unwrap_or("output.png"));
-    let method = String::from(matches.value_of("渲染方式").unwrap_or("normal"));
+fn method_arg(methods: Vec<&'static str>, default: Option<&'static str>) -> Arg<'static> {
+    let arg = Arg::new("method")
+        .short('m')
+        .long("method")
+        .value_name("METHOD")
+        .help("着色器或调试可视化模式")
+        .value_parser(PossibleValuesParser::new(methods));
+    match default {
+        Some(default) => arg.default_value(default),
+        None => arg,
+    }
+}
+
+// 任务3特有的参数
+fn mesh_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("model")
+            .long("model")
+            .value_name("OBJ")
+            .help("obj模型文件, 需带法线与纹理坐标; 默认纹理从同一目录查找"),
+        Arg::new("texture")
+            .long("texture")
+            .value_name("IMAGE")
+            .help("代替默认纹理: texture着色器的颜色贴图, bump/displacement的高度图"),
+        Arg::new("transfer")
+            .short('t')
+            .long("transfer")
+            .value_name("TRANSFER")
+            .help("输出编码")
+            .default_value("srgb")
+            .value_parser(["srgb", "linear"]),
+        Arg::new("exposure")
+            .long("exposure")
+            .value_name("EV")
+            .help("曝光补偿, 颜色乘以2^EV")
+            .default_value("0")
+            .allow_hyphen_values(true)
+            .value_parser(value_parser!(f64)),
+        Arg::new("tonemap")
+            .long("tonemap")
+            .value_name("OPERATOR")
+            .help("色调映射, none为直接截断")
+            .default_value("none")
+            .value_parser(["none", "reinhard", "aces", "uncharted2"]),
+        Arg::new("gamma")
+            .long("gamma")
+            .value_name("GAMMA")
+            .help("以c^(1/gamma)编码输出, 代替--transfer")
+            .value_parser(|s: &str| match s.parse::<f64>() {
+                Ok(g) if g > 0.0 => Ok(g),
+                Ok(_) => Err(String::from("gamma必须为正数")),
+                Err(e) => Err(e.to_string()),
+            }),
+        Arg::new("dither")
+            .long("dither")
+            .help("量化到8位前进行有序抖动")
+            .action(ArgAction::SetTrue),
+        Arg::new("shadow")
+            .long("shadow")
+            .value_name("FILTER")
+            .help("阴影模式")
+            .default_value("none")
+            .value_parser(["none", "hard", "pcf", "pcss"]),
+        Arg::new("deferred")
+            .long("deferred")
+            .help("使用延迟渲染")
+            .action(ArgAction::SetTrue),
+        Arg::new("gbuffer")
+            .long("gbuffer")
+            .value_name("PREFIX")
+            .help("导出G-buffer各通道, 参数为文件名前缀 (隐含--deferred)"),
+        Arg::new("ssao")
+            .long("ssao")
+            .help("开启屏幕空间环境光遮蔽")
+            .action(ArgAction::SetTrue),
+        Arg::new("ssao-radius")
+            .long("ssao-radius")
+            .value_name("RADIUS")
+            .help("SSAO采样半径 (view space), 默认0.5")
+            .value_parser(value_parser!(f64)),
+        Arg::new("ssao-samples")
+            .long("ssao-samples")
+            .value_name("N")
+            .help("SSAO每像素采样数, 默认16")
+            .value_parser(value_parser!(usize)),
+        Arg::new("ssao-strength")
+            .long("ssao-strength")
+            .value_name("STRENGTH")
+            .help("SSAO遮蔽强度, 默认1.0")
+            .value_parser(value_parser!(f64)),
+        Arg::new("ssao-blur")
+            .long("ssao-blur")
+            .value_name("PIXELS")
+            .help("SSAO模糊半径 (像素), 默认2")
+            .value_parser(value_parser!(usize)),
+        Arg::new("animate")
+            .long("animate")
+            .value_name("TARGET")
+            .help("转台动画, 输出编号的PNG序列")
+            .value_parser(["model", "camera", "light"]),
+        Arg::new("frames")
+            .long("frames")
+            .value_name("N")
+            .help("动画帧数")
+            .default_value("36")
+            .value_parser(value_parser!(u32).range(1..)),
+        Arg::new("gif")
+            .long("gif")
+            .value_name("FILE")
+            .help("动画同时输出为GIF (需要--animate)")
+            .requires("animate"),
+        Arg::new("stats")
+            .long("stats")
+            .help("打印三角形/片元计数与各阶段耗时")
+            .action(ArgAction::SetTrue),
+        Arg::new("hdr")
+            .long("hdr")
+            .value_name("FILE")
+            .help("导出未量化的浮点frame buffer, 按扩展名选择格式: .exr / .pfm / .hdr"),
+        Arg::new("depth")
+            .long("depth")
+            .value_name("FILE")
+            .help("导出深度缓冲 (屏幕空间z), 格式同--hdr"),
+        Arg::new("instances")
+            .long("instances")
+            .value_name("N")
+            .help("把N个模型排成网格, 以实例化方式绘制并逐个着色 (不用于--animate)")
+            .default_value("1")
+            .value_parser(value_parser!(u64).range(1..)),
+        Arg::new("early-z")
+            .long("early-z")
+            .help("前向渲染先做深度测试再着色, 并用8x8分块的分层深度跳过被遮挡的三角形与块")
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+fn parse_vec3(s: &str) -> std::result::Result<V3f, String> {
+    let v: Vec<f64> = s.split(',')
+        .map(|x| x.trim().parse::<f64>())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    match v.as_slice() {
+        &[x, y, z] => Ok(V3f::new(x, y, z)),
+        _ => Err(String::from("应为 x,y,z 三个数")),
+    }
+}
+
+fn string(m: &ArgMatches, id: &str) -> Option<String> {
+    m.get_one::<String>(id).cloned()
+}
 
-    let _ = match count{
-        1 => t1(),
-        2 => t2(),
-        3 => t3(filename,method),
-        _ => Ok(()),
+fn view_settings(m: &ArgMatches) -> ViewSettings {
+    ViewSettings {
+        width: *m.get_one::<u64>("width").unwrap() as usize,
+        height: *m.get_one::<u64>("height").unwrap() as usize,
+        eye: m.get_one::<V3f>("eye").copied(),
+        fov: *m.get_one::<f64>("fov").unwrap(),
+    }
+}
+
+// 任务1/2: --angle 绕z轴旋转, 先于 --transform 作用
+fn flat_transform(m: &ArgMatches) -> Transform {
+    let transform = m.get_one::<Transform>("transform").cloned().unwrap_or_default();
+    match m.get_one::<f64>("angle") {
+        Some(&angle) => Transform::new().rotate_z(angle).then(&transform),
+        None => transform,
+    }
+}
+
+// 任务3: 未指定 --transform 时 --angle 代替默认的140度; 否则先绕y轴旋转再作用 --transform
+fn mesh_transform(m: &ArgMatches) -> Option<Transform> {
+    let transform = m.get_one::<Transform>("transform").cloned();
+    match (m.get_one::<f64>("angle"), transform) {
+        (Some(&angle), Some(t)) => Some(Transform::new().rotate_y(angle).then(&t)),
+        (Some(&angle), None) => Some(Transform::new().uniform_scale(2.5).rotate_y(angle)),
+        (None, t) => t,
+    }
+}
+
+fn mesh_options(m: &ArgMatches) -> T3Options {
+    let output = OutputTransform {
+        exposure: *m.get_one::<f64>("exposure").unwrap(),
+        tone_map: ToneMap::from_name(m.get_one::<String>("tonemap").unwrap()).unwrap(),
+        transfer: match m.get_one::<f64>("gamma") {
+            Some(&g) => Transfer::Gamma(g),
+            None => Transfer::from_name(m.get_one::<String>("transfer").unwrap()).unwrap(),
+        },
+        dither: m.get_flag("dither"),
+    };
+    let shadow = ShadowFilter::from_name(m.get_one::<String>("shadow").unwrap())
+        .map(|filter| ShadowSettings { filter, ..Default::default() });
+    let animation = m.get_one::<String>("animate").map(|s| {
+        let mut anim = Animation::new(AnimationTarget::from_name(s).unwrap(), *m.get_one::<u32>("frames").unwrap() as usize);
+        anim.gif = string(m, "gif");
+        anim
+    });
+    let ssao = if m.get_flag("ssao") {
+        let default = SsaoSettings::default();
+        Some(SsaoSettings {
+            radius: m.get_one::<f64>("ssao-radius").copied().unwrap_or(default.radius),
+            samples: m.get_one::<usize>("ssao-samples").copied().unwrap_or(default.samples),
+            strength: m.get_one::<f64>("ssao-strength").copied().unwrap_or(default.strength),
+            blur: m.get_one::<usize>("ssao-blur").copied().unwrap_or(default.blur),
+            ..default
+        })
+    } else {
+        None
     };
-}
\ No newline at end of file
+    T3Options {
+        output,
+        view: view_settings(m),
+        model: string(m, "model"),
+        texture: string(m, "texture"),
+        shadow,
+        deferred: m.get_flag("deferred"),
+        ssao,
+        gbuffer: string(m, "gbuffer"),
+        transform: mesh_transform(m),
+        animation,
+        stats: m.get_flag("stats"),
+        hdr: string(m, "hdr"),
+        depth: string(m, "depth"),
+        instances: *m.get_one::<u64>("instances").unwrap() as usize,
+        early_z: m.get_flag("early-z"),
+    }
+}
+
+#[cfg(not(feature = "opencv"))]
+fn interactive_unavailable() -> Result<()> {
+    Err(Error::InvalidParameter(String::from("交互模式需要开启 opencv feature")))
+}
+
+fn run() -> Result<()> {
+    let matches = cli().get_matches();
+    let (name, m) = matches.subcommand().unwrap(); // subcommand_required
+    let filename = string(m, "output").unwrap();
+    let method = string(m, "method").unwrap_or_default();
+    let interactive = m.get_flag("interactive");
+
+    // 交互窗口需要 opencv feature, 否则只渲染一帧写入文件
+    match name {
+        #[cfg(feature = "opencv")]
+        "wireframe" if interactive => task1::t1(method, flat_transform(m), view_settings(m)),
+        #[cfg(feature = "opencv")]
+        "triangles" if interactive => task2::t2(method, flat_transform(m), view_settings(m)),
+        #[cfg(feature = "opencv")]
+        "mesh" if interactive => viewer::view(method, mesh_options(m)),
+        #[cfg(not(feature = "opencv"))]
+        _ if interactive => interactive_unavailable(),
+        "wireframe" => task1::save(&filename, &method, &flat_transform(m), &view_settings(m)),
+        "triangles" => task2::save(&filename, &method, &flat_transform(m), &view_settings(m)),
+        "mesh" => t3(filename, method, mesh_options(m)),
+        _ => Err(Error::InvalidParameter(format!("未知的子命令 {}", name))),
+    }
+}
@@ -0,0 +1,64 @@
+//! 带包围体的网格: 加载时把三角形按空间位置排序后分簇, 每簇与整个网格各有包围盒与包围球,
+//! rasterizer3 在三角形 setup 之前用它们做视锥剔除
+
+use std::ops::Range;
+use crate::bounds::{Aabb, Sphere};
+use crate::error::Result;
+use crate::triangle::Triangle;
+use crate::utils::{load_triangles, V3f};
+
+/// 每簇的三角形数
+pub const CLUSTER_SIZE: usize = 64;
+
+/// 一簇空间上相邻的三角形, range 为其在 Mesh::triangles 中的范围
+#[derive(Clone, Debug)]
+pub struct Cluster {
+    pub range: Range<usize>,
+    pub bounds: Aabb,
+    pub sphere: Sphere,
+}
+
+/// 可被多个节点共享的网格, 包围体都位于局部空间
+pub struct Mesh {
+    pub triangles: Vec<Triangle>,
+    pub bounds: Aabb,
+    pub sphere: Sphere,
+    pub clusters: Vec<Cluster>,
+}
+
+impl Mesh {
+    /// 三角形按重心的 Morton 码重新排序, 使每 CLUSTER_SIZE 个三角形组成一个紧凑的簇
+    pub fn new(mut triangles: Vec<Triangle>) -> Self {
+        let bounds = Aabb::from_triangles(&triangles);
+        let size = bounds.size().map(|x| x.max(1e-12));
+        triangles.sort_by_cached_key(|t| morton(&(centroid(t) - bounds.min).component_div(&size)));
+
+        let clusters = (0..triangles.len()).step_by(CLUSTER_SIZE).map(|start| {
+            let range = start..(start + CLUSTER_SIZE).min(triangles.len());
+            let cluster = &triangles[range.clone()];
+            Cluster { bounds: Aabb::from_triangles(cluster), sphere: Sphere::from_triangles(cluster), range }
+        }).collect();
+        let sphere = Sphere::from_triangles(&triangles);
+        Mesh { triangles, bounds, sphere, clusters }
+    }
+
+    pub fn load(obj_file: &str) -> Result<Self> {
+        Ok(Mesh::new(load_triangles(obj_file)?))
+    }
+}
+
+fn centroid(t: &Triangle) -> V3f {
+    (t.v[0].xyz() + t.v[1].xyz() + t.v[2].xyz()) / 3.0
+}
+
+// p 的各分量在 0~1 之间, 每轴量化为 10 位后交错
+fn morton(p: &V3f) -> u32 {
+    let spread = |x: f64| {
+        let mut v = (x.clamp(0.0, 1.0) * 1023.0) as u32;
+        v = (v | (v << 16)) & 0x030000FF;
+        v = (v | (v << 8)) & 0x0300F00F;
+        v = (v | (v << 4)) & 0x030C30C3;
+        (v | (v << 2)) & 0x09249249
+    };
+    spread(p.x) | (spread(p.y) << 1) | (spread(p.z) << 2)
+}
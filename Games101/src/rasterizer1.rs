@@ -1,8 +1,13 @@
-use std::collections::HashMap;
+//! 任务1的 rasterizer: 按下标缓冲绘制三角形线框 (Bresenham 画线)
+
 use super::utils::V3f;
 
 use nalgebra::{Matrix4, Vector3, Vector4};
+use crate::buffers::{Arena, Handle};
+use crate::topology::{assemble, flatten, out_of_range, Element};
 use crate::triangle::Triangle;
+use crate::debug::{DebugBuffer, DebugMode};
+use crate::error::{Error, Result};
 
 type V4d = Vector4<f64>;
 
@@ -13,35 +18,26 @@ pub enum Buffer {
     Both,
 }
 
-#[allow(dead_code)]
-pub enum Primitive {
-    Line,
-    Triangle,
-}
+pub use crate::topology::{Primitive, PRIMITIVE_RESTART};
 
 #[derive(Default)]
 pub struct Rasterizer {
     model: Matrix4<f64>,
     view: Matrix4<f64>,
     projection: Matrix4<f64>,
-    pos_buf: HashMap<usize, Vec<V3f>>,
-    ind_buf: HashMap<usize, Vec<Vector3<usize>>>,
+    pos_buf: Arena<Vec<V3f>>,
+    ind_buf: Arena<Vec<usize>>,
 
     frame_buf: Vec<V3f>,
     depth_buf: Vec<f64>,
     width: u64,
     height: u64,
-    next_id: usize,
+    debug: Option<DebugBuffer>,
 }
 
-#[derive(Clone, Copy,Debug)]
-pub struct PosBufId(usize);
-
-#[derive(Clone, Copy,Debug)]
-pub struct IndBufId(usize);
+pub type PosBufId = Handle<Vec<V3f>>;
 
-#[derive(Clone, Copy)]
-pub struct ColBufId(usize);
+pub type IndBufId = Handle<Vec<usize>>;
 
 impl Rasterizer {
     pub fn new(w: u64, h: u64) -> Self {
@@ -75,10 +71,14 @@ impl Rasterizer {
         frame_buf[ind] = *color;
     }
 
-    fn draw_line(begin: &V3f, end: &V3f, height: u64, width: u64, frame_buf: &mut Vec<V3f>) {
+    pub fn draw_line(begin: &V3f, end: &V3f, height: u64, width: u64, frame_buf: &mut Vec<V3f>) {
+        Self::draw_line_colored(begin, end, &Vector3::new(0.0, 255.0, 0.0), height, width, frame_buf);
+    }
+
+    /// Bresenham 画线, 超出画面的像素被忽略; rasterizer2 的线段图元也使用它
+    pub fn draw_line_colored(begin: &V3f, end: &V3f, line_color: &V3f, height: u64, width: u64, frame_buf: &mut Vec<V3f>) {
         let (x1, y1) = (begin.x, begin.y);
         let (x2, y2) = (end.x, end.y);
-        let line_color = Vector3::new(0.0, 255.0, 0.0);
         let (dx, dy, dx1, dy1, mut px, mut py): (f64, f64, f64, f64, f64, f64);
 
         dx = x2 - x1;
@@ -95,7 +95,7 @@ impl Rasterizer {
                 (x2, y2, x1)
             };
             let point = V3f::new(x.round(), y.round(), 1.0);
-            Self::set_pixel(height, width, frame_buf, &point, &line_color);
+            Self::set_pixel(height, width, frame_buf, &point, line_color);
             while x < xe {
                 x += 1.0;
                 if px < 0.0 {
@@ -107,7 +107,7 @@ impl Rasterizer {
                     px = px + 2.0 * (dy1 - dx1);
                 }
                 let point = V3f::new(x.round(), y.round(), 1.0);
-                Self::set_pixel(height, width, frame_buf, &point, &line_color);
+                Self::set_pixel(height, width, frame_buf, &point, line_color);
             }
         } else {
             let (mut x, mut y, ye) = if dy >= 0.0 {
@@ -116,7 +116,7 @@ impl Rasterizer {
                 (x2, y2, y1)
             };
             let point = V3f::new(x.round(), y.round(), 1.0);
-            Self::set_pixel(height, width, frame_buf, &point, &line_color);
+            Self::set_pixel(height, width, frame_buf, &point, line_color);
             while y < ye {
                 y += 1.0;
                 if py < 0.0 {
@@ -128,7 +128,7 @@ impl Rasterizer {
                     py += 2.0 * (dx1 - dy1);
                 }
                 let point = V3f::new(x.round(), y.round(), 1.0);
-                Self::set_pixel(height, width, frame_buf, &point, &line_color);
+                Self::set_pixel(height, width, frame_buf, &point, line_color);
             }
         }
     }
@@ -144,62 +144,112 @@ impl Rasterizer {
     pub fn set_projection(&mut self, projection: Matrix4<f64>) {
         self.projection = projection;
     }
-    fn get_next_id(&mut self) -> usize {
-        let res = self.next_id;
-        self.next_id += 1;
-        res
+    /// 调试可视化模式, 开启后三角形会被填充而不是画线框
+    pub fn set_debug_mode(&mut self, mode: Option<DebugMode>) {
+        self.debug = mode.map(|m| DebugBuffer::new(m, self.width as usize, self.height as usize));
     }
+
     pub fn load_position(&mut self, positions: &Vec<V3f>) -> PosBufId {
-        let id = self.get_next_id();
-        self.pos_buf.insert(id, positions.clone());
-        PosBufId(id)
+        self.pos_buf.insert(positions.clone())
     }
 
+    /// 三角形列表的下标, 等价于展开后以 Primitive::Triangle 绘制
     pub fn load_indices(&mut self, indices: &Vec<Vector3<usize>>) -> IndBufId {
-        let id = self.get_next_id();
-        self.ind_buf.insert(id, indices.clone());
-        IndBufId(id)
+        self.ind_buf.insert(flatten(indices))
+    }
+
+    /// 扁平的下标缓冲, 按 draw 时指定的拓扑解释, 可包含 PRIMITIVE_RESTART
+    pub fn load_index_buffer(&mut self, indices: &Vec<usize>) -> IndBufId {
+        self.ind_buf.insert(indices.clone())
+    }
+
+    pub fn update_position(&mut self, id: PosBufId, positions: &Vec<V3f>) -> Result<()> {
+        *self.pos_buf.try_get_mut(id)? = positions.clone();
+        Ok(())
+    }
+
+    pub fn update_indices(&mut self, id: IndBufId, indices: &Vec<Vector3<usize>>) -> Result<()> {
+        *self.ind_buf.try_get_mut(id)? = flatten(indices);
+        Ok(())
+    }
+
+    pub fn update_index_buffer(&mut self, id: IndBufId, indices: &Vec<usize>) -> Result<()> {
+        *self.ind_buf.try_get_mut(id)? = indices.clone();
+        Ok(())
     }
 
-    pub fn draw_triangle(&mut self, pos_buffer: PosBufId, ind_buffer: IndBufId, _typ: Primitive) {
-        let buf = &self.pos_buf[&pos_buffer.0];
-        let ind: &Vec<Vector3<usize>> = &self.ind_buf[&ind_buffer.0];
+    pub fn delete_position(&mut self, id: PosBufId) -> Result<()> {
+        self.pos_buf.remove(id).map(|_| ())
+    }
+
+    pub fn delete_indices(&mut self, id: IndBufId) -> Result<()> {
+        self.ind_buf.remove(id).map(|_| ())
+    }
+
+    /// 三角形画出三条边, 线段与点直接画出; 调试模式下只处理三角形
+    pub fn draw(&mut self, pos_buffer: PosBufId, ind_buffer: IndBufId, typ: Primitive) -> Result<()> {
+        let buf = self.pos_buf.try_get(pos_buffer)?;
+        let ind = self.ind_buf.try_get(ind_buffer)?;
+        if let Some(i) = out_of_range(ind, buf.len()) {
+            return Err(Error::InvalidGeometry(format!("顶点下标 {} 超出范围 ({} 个顶点)", i, buf.len())));
+        }
 
         let mvp = self.projection * self.view * self.model;
+        let (width, height) = (self.width, self.height);
+        let screen: Vec<V4d> = buf.iter().map(|&p| Rasterizer::to_screen(width, height, mvp, p)).collect();
+        let elements = assemble(ind, typ);
 
-        for i in ind {
-            let t = Rasterizer::get_triangle(self.width, self.height, buf, mvp, i);
-            Self::draw_line(&t.v[2].xyz(), &t.v[0].xyz(), self.height, self.width, &mut self.frame_buf);
-            Self::draw_line(&t.v[0].xyz(), &t.v[1].xyz(), self.height, self.width, &mut self.frame_buf);
-            Self::draw_line(&t.v[1].xyz(), &t.v[2].xyz(), self.height, self.width, &mut self.frame_buf);
+        if let Some(debug) = self.debug.as_mut() {
+            debug.clear();
+            let triangles = elements.iter().filter_map(|e| match e {
+                Element::Triangle(i) => Some(Rasterizer::get_triangle(&screen, i)),
+                _ => None,
+            });
+            for (id, t) in triangles.enumerate() {
+                debug.rasterize(&t, id);
+            }
+            self.frame_buf = debug.resolve(None);
+            return Ok(());
         }
+
+        for e in elements {
+            match e {
+                Element::Triangle(i) => {
+                    let t = Rasterizer::get_triangle(&screen, &i);
+                    Self::draw_line(&t.v[2].xyz(), &t.v[0].xyz(), height, width, &mut self.frame_buf);
+                    Self::draw_line(&t.v[0].xyz(), &t.v[1].xyz(), height, width, &mut self.frame_buf);
+                    Self::draw_line(&t.v[1].xyz(), &t.v[2].xyz(), height, width, &mut self.frame_buf);
+                }
+                Element::Line([a, b]) => Self::draw_line(&screen[a].xyz(), &screen[b].xyz(), height, width, &mut self.frame_buf),
+                Element::Point(i) => {
+                    let point = V3f::new(screen[i].x.round(), screen[i].y.round(), 1.0);
+                    Self::set_pixel(height, width, &mut self.frame_buf, &point, &Vector3::new(0.0, 255.0, 0.0));
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn get_triangle(width: u64, height: u64, buf: &Vec<V3f>, mvp: Matrix4<f64>, i: &Vector3<usize>) -> Triangle {
+    // MVP 与视口变换, 每个顶点只做一次
+    fn to_screen(width: u64, height: u64, mvp: Matrix4<f64>, p: V3f) -> V4d {
         let f1 = (50.0 - 0.1) / 2.0;
         let f2 = (50.0 + 0.1) / 2.0;
 
-        let mut t = Triangle::new();
-        let mut v =
-            vec![mvp * to_vec4(buf[i[0]], Some(1.0)),
-                 mvp * to_vec4(buf[i[1]], Some(1.0)),
-                 mvp * to_vec4(buf[i[2]], Some(1.0))];
+        let mut vert = mvp * to_vec4(p, Some(1.0));
+        vert /= vert.w;
+        vert.x = 0.5 * width as f64 * (vert.x + 1.0);
+        vert.y = 0.5 * height as f64 * (vert.y + 1.0);
+        vert.z = vert.z * f1 + f2;
+        vert
+    }
 
-        for vec in v.iter_mut() {
-            *vec = *vec / vec.w;
-        }
-        for vert in v.iter_mut() {
-            vert.x = 0.5 * width as f64 * (vert.x + 1.0);
-            vert.y = 0.5 * height as f64 * (vert.y + 1.0);
-            vert.z = vert.z * f1 + f2;
-        }
+    fn get_triangle(screen: &Vec<V4d>, i: &[usize; 3]) -> Triangle {
+        let mut t = Triangle::new();
         for j in 0..3 {
-            t.set_vertex(j, v[j]);
+            t.set_vertex(j, screen[i[j]]);
         }
 
-        t.set_color(0, 255.0, 0.0, 0.0);
-        t.set_color(1, 0.0, 255.0, 0.0);
-        t.set_color(2, 0.0, 0.0, 255.0);
+        t.color = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)];
         t
     }
 
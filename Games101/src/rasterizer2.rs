@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+//! 任务2的 rasterizer: 填充纯色三角形, 带深度测试 (MSAA 留作练习)
 
-use nalgebra::{Matrix4, Vector3, Vector4};
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+use crate::buffers::{Arena, Handle};
+use crate::rasterizer1;
+use crate::topology::{assemble, flatten, out_of_range, Element};
 use crate::triangle::Triangle;
+use crate::debug::{DebugBuffer, DebugMode};
+use crate::error::{Error, Result};
 
 #[allow(dead_code)]
 pub enum Buffer {
@@ -10,10 +15,83 @@ pub enum Buffer {
     Both,
 }
 
-#[allow(dead_code)]
-pub enum Primitive {
-    Line,
-    Triangle,
+pub use crate::topology::{Primitive, PRIMITIVE_RESTART};
+
+/// 顶点属性的语义; Custom 为自定义的浮点属性, 编号由调用者决定
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Attribute {
+    Position,
+    Normal,
+    TexCoord,
+    Color,
+    Custom(u32),
+}
+
+impl Attribute {
+    /// 每个顶点的分量数, Custom 不限
+    pub fn components(&self) -> Option<usize> {
+        match self {
+            Attribute::Position | Attribute::Normal | Attribute::Color => Some(3),
+            Attribute::TexCoord => Some(2),
+            Attribute::Custom(_) => None,
+        }
+    }
+
+    fn check(&self, buffer: &AttribBuffer) -> Result<()> {
+        match self.components() {
+            Some(n) if n != buffer.components =>
+                Err(Error::InvalidGeometry(format!("{:?} 需要 {} 个分量, 缓冲中为 {}", self, n, buffer.components))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 顶点属性缓冲: 每个顶点 components 个分量, 紧密排列
+#[derive(Clone, Debug)]
+pub struct AttribBuffer {
+    pub components: usize,
+    pub data: Vec<f64>,
+}
+
+impl AttribBuffer {
+    pub fn new(components: usize, data: Vec<f64>) -> Result<Self> {
+        if components == 0 || !data.len().is_multiple_of(components) {
+            return Err(Error::InvalidGeometry(format!("属性缓冲长度 {} 不是分量数 {} 的整数倍", data.len(), components)));
+        }
+        Ok(AttribBuffer { components, data })
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len() / self.components
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// 第 i 个顶点的属性
+    pub fn get(&self, i: usize) -> &[f64] {
+        &self.data[i * self.components..(i + 1) * self.components]
+    }
+
+    fn vec3(&self, i: usize) -> Vector3<f64> {
+        let v = self.get(i);
+        Vector3::new(v[0], v[1], v[2])
+    }
+}
+
+// 装配后的图元: 屏幕空间三角形及其顶点的自定义属性, 或带颜色的线段 / 点
+enum Assembled {
+    Triangle(Box<Triangle>, [Vec<f64>; 3]),
+    Line(Vector4<f64>, Vector4<f64>, Vector3<f64>),
+    Point(Vector4<f64>, Vector3<f64>),
+}
+
+/// 顶点数组对象 (VAO): 把若干属性缓冲与一个下标缓冲绑定在一起, 供 draw_elements 使用
+#[derive(Clone, Default, Debug)]
+pub struct VertexArray {
+    attributes: Vec<(Attribute, AttribBufId)>,
+    indices: Option<IndBufId>,
 }
 
 #[derive(Default, Clone)]
@@ -21,9 +99,9 @@ pub struct Rasterizer {
     model: Matrix4<f64>,
     view: Matrix4<f64>,
     projection: Matrix4<f64>,
-    pos_buf: HashMap<usize, Vec<Vector3<f64>>>,
-    ind_buf: HashMap<usize, Vec<Vector3<usize>>>,
-    col_buf: HashMap<usize, Vec<Vector3<f64>>>,
+    resources: Resources,
+    /// 当前三角形三个顶点的自定义属性 (按绑定顺序拼接), 供 rasterize_triangle 插值
+    varyings: [Vec<f64>; 3],
 
     frame_buf: Vec<Vector3<f64>>,
     depth_buf: Vec<f64>,
@@ -32,17 +110,26 @@ pub struct Rasterizer {
     // depth_sample: Vec<f64>,
     width: u64,
     height: u64,
-    next_id: usize,
+    debug: Option<DebugBuffer>,
 }
 
-#[derive(Clone, Copy)]
-pub struct PosBufId(usize);
+/// 顶点资源, 与 frame buffer 等渲染目标分开存放, draw 时只借用
+#[derive(Default, Clone)]
+struct Resources {
+    attributes: Arena<AttribBuffer>,
+    indices: Arena<Vec<usize>>,
+    vertex_arrays: Arena<VertexArray>,
+}
 
 #[derive(Clone, Copy)]
-pub struct IndBufId(usize);
+pub struct PosBufId(AttribBufId);
 
 #[derive(Clone, Copy)]
-pub struct ColBufId(usize);
+pub struct ColBufId(AttribBufId);
+
+pub type AttribBufId = Handle<AttribBuffer>;
+pub type IndBufId = Handle<Vec<usize>>;
+pub type VaoId = Handle<VertexArray>;
 
 impl Rasterizer {
     pub fn new(w: u64, h: u64) -> Self {
@@ -90,69 +177,239 @@ impl Rasterizer {
         self.projection = projection;
     }
 
-    fn get_next_id(&mut self) -> usize {
-        let res = self.next_id;
-        self.next_id += 1;
-        res
+    /// 调试可视化模式, 开启后 draw 不再调用 rasterize_triangle
+    pub fn set_debug_mode(&mut self, mode: Option<DebugMode>) {
+        self.debug = mode.map(|m| DebugBuffer::new(m, self.width as usize, self.height as usize));
+    }
+
+    /// 加载一个顶点属性缓冲, data 的长度应为 components 的整数倍
+    pub fn load_attribute(&mut self, components: usize, data: &Vec<f64>) -> Result<AttribBufId> {
+        Ok(self.resources.attributes.insert(AttribBuffer::new(components, data.clone())?))
+    }
+
+    pub fn load_vec3(&mut self, data: &Vec<Vector3<f64>>) -> AttribBufId {
+        let data = data.iter().flat_map(|v| [v.x, v.y, v.z]).collect();
+        self.resources.attributes.insert(AttribBuffer { components: 3, data })
+    }
+
+    pub fn load_vec2(&mut self, data: &Vec<Vector2<f64>>) -> AttribBufId {
+        let data = data.iter().flat_map(|v| [v.x, v.y]).collect();
+        self.resources.attributes.insert(AttribBuffer { components: 2, data })
+    }
+
+    fn update_vec3(&mut self, id: AttribBufId, data: &Vec<Vector3<f64>>) -> Result<()> {
+        let data = data.iter().flat_map(|v| [v.x, v.y, v.z]).collect();
+        *self.resources.attributes.try_get_mut(id)? = AttribBuffer { components: 3, data };
+        Ok(())
+    }
+
+    pub fn attribute(&self, id: AttribBufId) -> Option<&AttribBuffer> {
+        self.resources.attributes.get(id)
+    }
+
+    /// 替换属性缓冲的内容, 分量数可以改变 (已绑定到 VAO 时在下次绘制时检查)
+    pub fn update_attribute(&mut self, id: AttribBufId, components: usize, data: &Vec<f64>) -> Result<()> {
+        let buffer = AttribBuffer::new(components, data.clone())?;
+        *self.resources.attributes.try_get_mut(id)? = buffer;
+        Ok(())
+    }
+
+    /// 删除后 id 失效, 绑定了它的 VAO 在绘制时会返回错误
+    pub fn delete_attribute(&mut self, id: AttribBufId) -> Result<()> {
+        self.resources.attributes.remove(id).map(|_| ())
     }
 
     pub fn load_position(&mut self, positions: &Vec<Vector3<f64>>) -> PosBufId {
-        let id = self.get_next_id();
-        self.pos_buf.insert(id, positions.clone());
-        PosBufId(id)
+        PosBufId(self.load_vec3(positions))
+    }
+
+    pub fn update_position(&mut self, id: PosBufId, positions: &Vec<Vector3<f64>>) -> Result<()> {
+        self.update_vec3(id.0, positions)
     }
 
+    pub fn delete_position(&mut self, id: PosBufId) -> Result<()> {
+        self.delete_attribute(id.0)
+    }
+
+    /// 三角形列表的下标, 等价于展开后以 Primitive::Triangle 绘制
     pub fn load_indices(&mut self, indices: &Vec<Vector3<usize>>) -> IndBufId {
-        let id = self.get_next_id();
-        self.ind_buf.insert(id, indices.clone());
-        IndBufId(id)
+        self.resources.indices.insert(flatten(indices))
     }
 
+    /// 扁平的下标缓冲, 按 draw 时指定的拓扑解释, 可包含 PRIMITIVE_RESTART
+    pub fn load_index_buffer(&mut self, indices: &Vec<usize>) -> IndBufId {
+        self.resources.indices.insert(indices.clone())
+    }
+
+    pub fn update_indices(&mut self, id: IndBufId, indices: &Vec<Vector3<usize>>) -> Result<()> {
+        *self.resources.indices.try_get_mut(id)? = flatten(indices);
+        Ok(())
+    }
+
+    pub fn update_index_buffer(&mut self, id: IndBufId, indices: &Vec<usize>) -> Result<()> {
+        *self.resources.indices.try_get_mut(id)? = indices.clone();
+        Ok(())
+    }
+
+    pub fn delete_indices(&mut self, id: IndBufId) -> Result<()> {
+        self.resources.indices.remove(id).map(|_| ())
+    }
+
+    /// 颜色分量在 0~255 之间
     pub fn load_colors(&mut self, colors: &Vec<Vector3<f64>>) -> ColBufId {
-        let id = self.get_next_id();
-        self.col_buf.insert(id, colors.clone());
-        ColBufId(id)
+        ColBufId(self.load_vec3(colors))
+    }
+
+    pub fn update_colors(&mut self, id: ColBufId, colors: &Vec<Vector3<f64>>) -> Result<()> {
+        self.update_vec3(id.0, colors)
+    }
+
+    pub fn delete_colors(&mut self, id: ColBufId) -> Result<()> {
+        self.delete_attribute(id.0)
+    }
+
+    pub fn create_vertex_array(&mut self) -> VaoId {
+        self.resources.vertex_arrays.insert(VertexArray::default())
+    }
+
+    /// 只删除 VAO 本身, 其中绑定的缓冲不受影响
+    pub fn delete_vertex_array(&mut self, vao: VaoId) -> Result<()> {
+        self.resources.vertex_arrays.remove(vao).map(|_| ())
     }
 
-    pub fn draw(&mut self, pos_buffer: PosBufId, ind_buffer: IndBufId, col_buffer: ColBufId, _typ: Primitive) {
-        let buf = &self.clone().pos_buf[&pos_buffer.0];
-        let ind: &Vec<Vector3<usize>> = &self.clone().ind_buf[&ind_buffer.0];
-        let col = &self.clone().col_buf[&col_buffer.0];
+    /// 把属性缓冲绑定到 VAO 的某个语义上, 同一语义重复绑定时以最后一次为准
+    pub fn bind_attribute(&mut self, vao: VaoId, attribute: Attribute, buffer: AttribBufId) -> Result<()> {
+        attribute.check(self.resources.attributes.try_get(buffer)?)?;
+        let vertex_array = self.resources.vertex_arrays.try_get_mut(vao)?;
+        vertex_array.attributes.retain(|(a, _)| *a != attribute);
+        vertex_array.attributes.push((attribute, buffer));
+        Ok(())
+    }
+
+    pub fn bind_indices(&mut self, vao: VaoId, indices: IndBufId) -> Result<()> {
+        self.resources.indices.try_get(indices)?;
+        self.resources.vertex_arrays.try_get_mut(vao)?.indices = Some(indices);
+        Ok(())
+    }
+
+    /// 按位置/下标/颜色缓冲绘制, 等价于绑定到一个临时 VAO 后调用 draw_elements
+    pub fn draw(&mut self, pos_buffer: PosBufId, ind_buffer: IndBufId, col_buffer: ColBufId, typ: Primitive) -> Result<()> {
+        let attributes = [(Attribute::Position, pos_buffer.0), (Attribute::Color, col_buffer.0)];
+        let primitives = self.assemble(&attributes, Some(ind_buffer), typ)?;
+        self.rasterize_all(primitives);
+        Ok(())
+    }
+
+    /// 按 VAO 中的下标缓冲与给定拓扑绘制; 没有绑定颜色时为白色
+    /// 三角形交给 rasterize_triangle, 线段与点直接以第一个顶点的颜色画出, 不做深度测试
+    pub fn draw_elements(&mut self, vao: VaoId, typ: Primitive) -> Result<()> {
+        let vertex_array = self.resources.vertex_arrays.try_get(vao)?;
+        let primitives = self.assemble(&vertex_array.attributes, vertex_array.indices, typ)?;
+        self.rasterize_all(primitives);
+        Ok(())
+    }
+
+    // 顶点阶段与图元装配, 只借用顶点资源
+    fn assemble(&self, bindings: &[(Attribute, AttribBufId)], indices: Option<IndBufId>, typ: Primitive) -> Result<Vec<Assembled>> {
+        let mut attributes: Vec<(Attribute, &AttribBuffer)> = Vec::new();
+        for &(attribute, id) in bindings {
+            let buffer = self.resources.attributes.try_get(id)?;
+            attribute.check(buffer)?;
+            attributes.push((attribute, buffer));
+        }
+        let find = |attribute: Attribute| attributes.iter().find(|(a, _)| *a == attribute).map(|(_, b)| *b);
+        let pos = find(Attribute::Position)
+            .ok_or_else(|| Error::InvalidGeometry(String::from("VAO 没有绑定 Position 属性")))?;
+        let ind = self.resources.indices
+            .try_get(indices.ok_or_else(|| Error::InvalidGeometry(String::from("VAO 没有绑定下标缓冲")))?)?;
+        let vertex_count = attributes.iter().map(|(_, b)| b.len()).min().unwrap_or(0);
+        if let Some(i) = out_of_range(ind, vertex_count) {
+            return Err(Error::InvalidGeometry(format!("顶点下标 {} 超出范围 (各属性中最少有 {} 个顶点)", i, vertex_count)));
+        }
 
         let f1 = (50.0 - 0.1) / 2.0;
         let f2 = (50.0 + 0.1) / 2.0;
 
+        // 每个顶点只变换一次, 共享顶点的三角形直接取结果
         let mvp = self.projection * self.view * self.model;
+        let screen: Vec<Vector4<f64>> = (0..pos.len()).map(|i| {
+            let mut vert = mvp * to_vec4(pos.vec3(i), Some(1.0)); // homogeneous coordinates
+            vert /= vert.w;
+            vert.x = 0.5 * self.width as f64 * (vert.x + 1.0);
+            vert.y = 0.5 * self.height as f64 * (vert.y + 1.0);
+            vert.z = vert.z * f1 + f2;
+            vert
+        }).collect();
 
-        for i in ind {
+        let (col, normal, tex_coord) = (find(Attribute::Color), find(Attribute::Normal), find(Attribute::TexCoord));
+        let custom: Vec<&AttribBuffer> = attributes.iter()
+            .filter(|(a, _)| matches!(a, Attribute::Custom(_)))
+            .map(|(_, b)| *b)
+            .collect();
+        let color = |i: usize| col.map_or(Vector3::new(255.0, 255.0, 255.0), |b| b.vec3(i));
+        let mut primitives = Vec::new();
+        for e in assemble(ind, typ) {
+            let i = match e {
+                Element::Triangle(i) => i,
+                Element::Line([a, b]) => {
+                    primitives.push(Assembled::Line(screen[a], screen[b], color(a)));
+                    continue;
+                }
+                Element::Point(i) => {
+                    primitives.push(Assembled::Point(screen[i], color(i)));
+                    continue;
+                }
+            };
             let mut t = Triangle::new();
-            let mut v =
-                vec![mvp * to_vec4(buf[i[0]], Some(1.0)), // homogeneous coordinates
-                     mvp * to_vec4(buf[i[1]], Some(1.0)), 
-                     mvp * to_vec4(buf[i[2]], Some(1.0))];
-    
-            for vec in v.iter_mut() {
-                *vec = *vec / vec.w;
-            }
-            for vert in v.iter_mut() {
-                vert.x = 0.5 * self.width as f64 * (vert.x + 1.0);
-                vert.y = 0.5 * self.height as f64 * (vert.y + 1.0);
-                vert.z = vert.z * f1 + f2;
-            }
+            let mut varyings: [Vec<f64>; 3] = Default::default();
             for j in 0..3 {
-                // t.set_vertex(j, Vector3::new(v[j].x, v[j].y, v[j].z));
-                t.set_vertex(j, v[j]);
-                t.set_vertex(j, v[j]);
-                t.set_vertex(j, v[j]);
+                t.set_vertex(j, screen[i[j]]);
+                let c = color(i[j]);
+                t.set_color(j, c[0], c[1], c[2])?;
+                if let Some(n) = normal {
+                    t.set_normal(j, n.vec3(i[j]));
+                }
+                if let Some(uv) = tex_coord {
+                    let uv = uv.get(i[j]);
+                    t.set_tex_coord(j, uv[0], uv[1]);
+                }
+                varyings[j] = custom.iter().flat_map(|b| b.get(i[j]).iter().copied()).collect();
+            }
+            primitives.push(Assembled::Triangle(Box::new(t), varyings));
+        }
+        Ok(primitives)
+    }
+
+    // 调试模式下只处理三角形, id 为三角形的序号
+    fn rasterize_all(&mut self, primitives: Vec<Assembled>) {
+        if let Some(debug) = self.debug.as_mut() {
+            debug.clear();
+        }
+        let (width, height) = (self.width, self.height);
+        let mut id = 0;
+        for p in primitives {
+            match (p, self.debug.as_mut()) {
+                (Assembled::Triangle(t, _), Some(debug)) => {
+                    debug.rasterize(&t, id);
+                    id += 1;
+                }
+                (Assembled::Triangle(t, varyings), None) => {
+                    self.varyings = varyings;
+                    self.rasterize_triangle(&t);
+                }
+                (_, Some(_)) => {}
+                (Assembled::Line(a, b, color), None) =>
+                    rasterizer1::Rasterizer::draw_line_colored(&a.xyz(), &b.xyz(), &color, height, width, &mut self.frame_buf),
+                (Assembled::Point(p, color), None) => {
+                    let (x, y) = (p.x.round(), p.y.round());
+                    if x >= 0.0 && x < width as f64 && y >= 0.0 && y < height as f64 {
+                        self.frame_buf[((height as f64 - 1.0 - y) * width as f64 + x) as usize] = color;
+                    }
+                }
             }
-            let col_x = col[i[0]];
-            let col_y = col[i[1]];
-            let col_z = col[i[2]];
-            t.set_color(0, col_x[0], col_x[1], col_x[2]);
-            t.set_color(1, col_y[0], col_y[1], col_y[2]);
-            t.set_color(2, col_z[0], col_z[1], col_z[2]);
-
-            self.rasterize_triangle(&t);
+        }
+        if let Some(debug) = &self.debug {
+            self.frame_buf = debug.resolve(None);
         }
     }
 
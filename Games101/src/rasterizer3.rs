@@ -1,7 +1,22 @@
+//! 任务3的 rasterizer: 透视校正插值, 调用顶点/片元着色器, 可选阴影、延迟渲染与 SSAO
+
+use std::cell::Cell;
 use std::rc::Rc;
+use std::time::Instant;
 
 use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
-use crate::shader::{FragmentShaderPayload, VertexShaderPayload};
+use crate::coverage;
+use crate::error::{Error, Result};
+use crate::shader::{default_lights, FragmentShaderPayload, Light, VertexShaderPayload};
+use crate::shadow::{ShadowMap, ShadowMaps, ShadowSettings};
+use crate::gbuffer::GBuffer;
+use crate::ssao::{compute_ssao, SsaoSettings};
+use crate::debug::{DebugBuffer, DebugMode};
+use crate::instance::Instance;
+use crate::bounds::{Aabb, Frustum, Sphere};
+use crate::mesh::{Cluster, Mesh};
+use crate::hiz::HiZ;
+use crate::stats::RenderStats;
 use crate::texture::Texture;
 use crate::triangle::Triangle;
 
@@ -18,12 +33,30 @@ pub enum Primitive {
     Triangle,
 }
 
+/// setup 的结果: (三角形下标, 屏幕空间三角形, view space 顶点)
+pub type ScreenTriangle = (usize, Triangle, Vec<Vector3<f64>>);
+
 #[derive(Default)]
 pub struct Rasterizer {
     model: Matrix4<f64>,
     view: Matrix4<f64>,
     projection: Matrix4<f64>,
     texture: Option<Texture>,
+    lights: Vec<Light>,
+    shadow_settings: Option<ShadowSettings>,
+    shadow_maps: Option<ShadowMaps>,
+    deferred: bool,
+    gbuffer: Option<GBuffer>,
+    ssao: Option<SsaoSettings>,
+    ao_buf: Option<Vec<f64>>,
+    debug: Option<DebugBuffer>,
+    wireframe: bool,
+    cull_backfaces: bool,
+    hiz: Option<HiZ>, // 开启 early-Z 时的分层深度
+    stats: Cell<RenderStats>, // 着色时 payload 借用着 self, 因此用 Cell 计数
+    instances: Vec<Instance>,
+    instance: usize, // 正在处理的实例与三角形材质, 由 vertex_stage 与 fragment_payload 写入 payload
+    material: usize,
 
     vert_shader: Option<fn(&VertexShaderPayload) -> Vector3<f64>>,
     fragment_shader: Option<fn(&FragmentShaderPayload) -> Vector3<f64>>,
@@ -50,6 +83,7 @@ impl Rasterizer {
         r.frame_buf.resize((w * h) as usize, Vector3::zeros());
         r.depth_buf.resize((w * h) as usize, 0.0);
         r.texture = None;
+        r.lights = default_lights();
         r
     }
 
@@ -62,6 +96,7 @@ impl Rasterizer {
         frame_buf[ind as usize] = *color;
     }
 
+    /// 清除深度时调试缓冲一并清除, 因此同一帧内的多次 draw 会叠加
     pub fn clear(&mut self, buff: Buffer) {
         match buff {
             Buffer::Color =>
@@ -73,6 +108,14 @@ impl Rasterizer {
                 self.depth_buf.fill(f64::MAX);
             }
         }
+        if matches!(buff, Buffer::Depth | Buffer::Both) {
+            if let Some(debug) = self.debug.as_mut() {
+                debug.clear();
+            }
+            if let Some(hiz) = self.hiz.as_mut() {
+                hiz.clear();
+            }
+        }
     }
     pub fn set_model(&mut self, model: Matrix4<f64>) {
         self.model = model;
@@ -90,6 +133,52 @@ impl Rasterizer {
         self.texture = Some(tex); 
     }
 
+    pub fn set_lights(&mut self, lights: Vec<Light>) {
+        self.lights = lights;
+    }
+
+    /// None 表示关闭阴影
+    pub fn set_shadows(&mut self, settings: Option<ShadowSettings>) {
+        self.shadow_settings = settings;
+        self.shadow_maps = None;
+    }
+
+    /// 延迟渲染: 先光栅化到 G-buffer, 再逐像素做一次全屏着色
+    pub fn set_deferred(&mut self, deferred: bool) {
+        self.deferred = deferred;
+    }
+
+    /// None 表示关闭 SSAO
+    pub fn set_ssao(&mut self, settings: Option<SsaoSettings>) {
+        self.ssao = settings;
+    }
+
+    /// 调试可视化模式, 开启后 draw 不再调用着色器
+    pub fn set_debug_mode(&mut self, mode: Option<DebugMode>) {
+        self.debug = mode.map(|m| DebugBuffer::new(m, self.width as usize, self.height as usize));
+    }
+
+    /// 在着色结果上叠加三角形边框
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.wireframe = wireframe;
+    }
+
+    /// 前向渲染改用先做深度测试再着色的 early-Z 光栅化, 关闭时调用 rasterize_triangle
+    pub fn set_early_z(&mut self, enabled: bool) {
+        self.hiz = if enabled { Some(HiZ::new(self.width as usize, self.height as usize)) } else { None };
+    }
+
+    /// 剔除背面 (屏幕空间中顺时针) 三角形
+    pub fn set_backface_culling(&mut self, cull: bool) {
+        self.cull_backfaces = cull;
+    }
+
+    /// 最近一次延迟渲染得到的 G-buffer
+    pub fn gbuffer(&self) -> Option<&GBuffer> {
+        self.gbuffer.as_ref()
+    }
+
+    /// 顶点着色器在 MVP 之前逐顶点调用, 返回模型空间位置; 视锥剔除仍使用网格加载时的包围盒
     pub fn set_vertex_shader(&mut self, vert_shader: fn(&VertexShaderPayload) -> Vector3<f64>) {
         self.vert_shader = Some(vert_shader);
     }
@@ -98,21 +187,422 @@ impl Rasterizer {
         self.fragment_shader = Some(frag_shader);
     }
 
-    pub fn draw(&mut self, triangles: &Vec<Triangle>) {
+    /// 变换到屏幕空间 (保留原下标), 丢弃视锥外的三角形并按需剔除背面,
+    /// 返回 (下标, 屏幕空间三角形, view space 顶点); model-view 矩阵不可逆时返回错误
+    pub fn screen_triangles(&self, triangles: &Vec<Triangle>) -> Result<Vec<ScreenTriangle>> {
+        self.setup_triangles(triangles.iter().enumerate(), triangles.len())
+    }
+
+    /// 与 screen_triangles 相同, 但先用包围球与包围盒检验整个网格和每一簇,
+    /// 完全位于视锥外的簇直接跳过, 其三角形计入 triangles_clipped
+    pub fn visible_triangles(&self, mesh: &Mesh) -> Result<Vec<ScreenTriangle>> {
+        let start = Instant::now();
+        let frustum = Frustum::from_matrix(&(self.projection * self.view * self.model));
+        let visible = |sphere: &Sphere, bounds: &Aabb| frustum.intersects_sphere(sphere) && frustum.intersects_aabb(bounds);
+        let clusters: Vec<&Cluster> = if visible(&mesh.sphere, &mesh.bounds) {
+            mesh.clusters.iter().filter(|c| visible(&c.sphere, &c.bounds)).collect()
+        } else {
+            vec![]
+        };
+        let culled = mesh.clusters.len() - clusters.len();
+        self.count(|s| {
+            s.clusters_tested += mesh.clusters.len();
+            s.clusters_culled += culled;
+            s.vertex_time += start.elapsed();
+        });
+        let triangles = clusters.into_iter().flat_map(|c| c.range.clone()).map(|id| (id, &mesh.triangles[id]));
+        self.setup_triangles(triangles, mesh.triangles.len())
+    }
+
+    // submitted 为参与本次 setup 的三角形总数, 未出现在 triangles 中的视为已裁剪
+    fn setup_triangles<'a>(&self, triangles: impl Iterator<Item = (usize, &'a Triangle)>, submitted: usize)
+                           -> Result<Vec<ScreenTriangle>> {
+        let start = Instant::now();
         let mvp = self.projection * self.view * self.model;
+        let visible: Vec<ScreenTriangle> = triangles
+            .map(|(id, t)| (id, self.vertex_stage(t, self.instance)))
+            .filter(|(_, t)| !Self::is_outside_frustum(t, &mvp))
+            .map(|(id, t)| {
+                let (t, view_pos) = Self::get_new_tri(&t, self.view, self.model, mvp, (self.width, self.height))?;
+                Ok((id, t, view_pos))
+            })
+            .collect::<Result<_>>()?;
+        let clipped = submitted - visible.len();
+        self.count(|s| {
+            s.triangles_clipped += clipped;
+            s.vertex_time += start.elapsed();
+        });
+
+        let start = Instant::now();
+        let screen: Vec<_> = visible.into_iter()
+            .filter(|(_, t, _)| !self.cull_backfaces || Self::is_front_facing(t))
+            .collect();
+        let culled = submitted - clipped - screen.len();
+        self.count(|s| {
+            s.triangles_culled += culled;
+            s.setup_time += start.elapsed();
+        });
+        Ok(screen)
+    }
+
+    /// 以当前的 model 矩阵绘制一次
+    pub fn draw(&mut self, triangles: &Vec<Triangle>) -> Result<RenderStats> {
+        self.draw_instanced(triangles, &[Instance::new(self.model)])
+    }
+
+    /// 以当前的 model 矩阵绘制网格, 视锥外的簇在 setup 之前被剔除
+    pub fn draw_mesh(&mut self, mesh: &Mesh) -> Result<RenderStats> {
+        self.draw_mesh_instanced(mesh, &[Instance::new(self.model)])
+    }
+
+    /// 同一网格按每个实例的 model 矩阵各绘制一次, 阴影、G-buffer 与 SSAO 对所有实例只计算一遍
+    /// 绘制结束后 model 矩阵恢复为调用前的值; 某个实例的 model-view 矩阵不可逆时不绘制任何内容并返回错误
+    pub fn draw_instanced(&mut self, triangles: &Vec<Triangle>, instances: &[Instance]) -> Result<RenderStats> {
+        self.render(triangles, None, instances)
+    }
+
+    /// 与 draw_instanced 相同, 每个实例分别做网格与簇的视锥剔除
+    pub fn draw_mesh_instanced(&mut self, mesh: &Mesh, instances: &[Instance]) -> Result<RenderStats> {
+        self.render(&mesh.triangles, Some(mesh), instances)
+    }
+
+    fn render(&mut self, triangles: &Vec<Triangle>, mesh: Option<&Mesh>, instances: &[Instance]) -> Result<RenderStats> {
+        self.stats.set(RenderStats { triangles_submitted: triangles.len() * instances.len(), ..Default::default() });
+        let model = self.model;
+        self.instances = instances.to_vec();
+        let screen: Result<Vec<_>> = instances.iter().enumerate().map(|(k, instance)| {
+            self.model = instance.model;
+            self.instance = k;
+            match mesh {
+                Some(mesh) => self.visible_triangles(mesh),
+                None => self.screen_triangles(triangles),
+            }
+        }).collect();
+        self.model = model;
+        let screen = screen?;
 
-        // 遍历每个小三角形
-        for triangle in triangles { 
-            self.rasterize_triangle(&triangle, mvp); 
+        if let Some(mut debug) = self.debug.take() {
+            for (k, screen) in screen.iter().enumerate() {
+                for (id, t, _) in screen {
+                    debug.rasterize(t, k * triangles.len() + id);
+                }
+            }
+            self.frame_buf = debug.resolve(self.texture.as_ref().map(|t| (t.width, t.height)));
+            self.debug = Some(debug);
+            screen.iter().for_each(|screen| self.draw_wireframe(screen));
+            return Ok(self.stats.get());
+        }
+
+        // 先从每个光源渲染深度, 供片元着色器查询
+        let start = Instant::now();
+        self.shadow_maps = match self.shadow_settings {
+            None => None,
+            Some(settings) => Some(self.render_shadow_maps(triangles, settings)),
+        };
+        self.count(|s| s.setup_time += start.elapsed());
+
+        // 延迟渲染与 SSAO 都需要先做一遍几何 pass
+        let start = Instant::now();
+        self.gbuffer = None;
+        let gbuffer = if self.deferred || self.ssao.is_some() {
+            let mut gbuffer = GBuffer::new(self.width as usize, self.height as usize);
+            for (k, screen) in screen.iter().enumerate() {
+                for (_, t, view_pos) in screen {
+                    self.rasterize_gbuffer(&mut gbuffer, t, view_pos, k);
+                }
+            }
+            Some(gbuffer)
+        } else {
+            None
+        };
+        self.count(|s| s.coverage_time += start.elapsed());
+
+        let start = Instant::now();
+        self.ao_buf = match (&gbuffer, &self.ssao) {
+            (Some(gbuffer), Some(settings)) => Some(compute_ssao(gbuffer, &self.projection, settings)),
+            _ => None,
+        };
+        self.count(|s| s.setup_time += start.elapsed());
+
+        // 着色时间单独计入 shading_time, 这里只记录其余部分
+        let start = Instant::now();
+        let shading_before = self.stats.get().shading_time;
+        if let (true, Some(gbuffer)) = (self.deferred, gbuffer) {
+            self.shade_gbuffer(&gbuffer);
+            self.gbuffer = Some(gbuffer);
+        } else {
+            // 遍历每个实例的每个小三角形
+            for (k, screen) in screen.iter().enumerate() {
+                self.instance = k;
+                for (_, t, view_pos) in screen {
+                    self.material = t.material_id;
+                    if self.hiz.is_some() {
+                        self.rasterize_early_z(t, view_pos);
+                    } else {
+                        self.rasterize_triangle(t, view_pos);
+                    }
+                }
+            }
+        }
+        let shading = self.stats.get().shading_time - shading_before;
+        let covered = self.depth_buf.iter().filter(|z| **z < f64::MAX).count() as u64;
+        self.count(|s| {
+            s.coverage_time += start.elapsed().saturating_sub(shading);
+            s.pixels_covered = covered;
+        });
+        screen.iter().for_each(|screen| self.draw_wireframe(screen));
+        Ok(self.stats.get())
+    }
+
+    // 顶点阶段: 应用第 k 个实例的材质覆盖, 再逐顶点调用顶点着色器
+    fn vertex_stage(&self, t: &Triangle, k: usize) -> Triangle {
+        let mut t = t.clone();
+        let instance = self.instances.get(k);
+        if let Some(material_id) = instance.and_then(|instance| instance.material_id) {
+            t.material_id = material_id;
+        }
+        if let Some(shader) = self.vert_shader {
+            let tint = instance.map_or(Vector3::repeat(1.0), |instance| instance.tint);
+            for v in t.v.iter_mut() {
+                let payload = VertexShaderPayload { position: v.xyz(), instance_id: k, tint, material_id: t.material_id };
+                *v = to_vec4(shader(&payload), Some(v.w));
+            }
         }
+        t
     }
 
-    pub fn rasterize_triangle(&mut self, triangle: &Triangle, mvp: Matrix4<f64>) {
+    /// t 已经过 MVP 与视口变换 (见 get_new_tri), view_pos 为三个顶点在 view space 中的坐标
+    pub fn rasterize_triangle(&mut self, t: &Triangle, view_pos: &Vec<Vector3<f64>>) {
         /*  Implement your code here  */
+        // 提示: 片元着色器的输入请通过 self.fragment_payload(...) 构造, 其中已带上纹理、光源、阴影与 SSAO
+        //       深度测试请使用 self.depth_test(ind, z), 着色请使用 self.shade(&payload), 以便 --stats 统计
 
 
     }
-    
+
+    /// 深度测试, 通过时写入深度; ind 为像素在 frame buffer 中的下标
+    pub fn depth_test(&mut self, ind: usize, z: f64) -> bool {
+        let passed = z < self.depth_buf[ind];
+        if passed {
+            self.depth_buf[ind] = z;
+        }
+        self.count(|s| {
+            s.fragments_tested += 1;
+            s.fragments_passed += passed as u64;
+        });
+        passed
+    }
+
+    /// 调用片元着色器, 未设置着色器时返回黑色
+    pub fn shade(&self, payload: &FragmentShaderPayload) -> Vector3<f64> {
+        let start = Instant::now();
+        let color = self.fragment_shader.map_or(Vector3::zeros(), |shader| shader(payload));
+        self.count(|s| {
+            s.fragments_shaded += 1;
+            s.shading_time += start.elapsed();
+        });
+        color
+    }
+
+    fn count(&self, f: impl FnOnce(&mut RenderStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    // 三个顶点都在同一裁剪平面之外时整个三角形不可见 (在裁剪空间中判断, 相机后方的顶点也能正确处理)
+    fn is_outside_frustum(t: &Triangle, mvp: &Matrix4<f64>) -> bool {
+        let clip: Vec<Vector4<f64>> = t.v.iter().map(|v| mvp * v).collect();
+        (0..3).any(|axis| {
+            clip.iter().all(|p| p[axis] < -p.w) || clip.iter().all(|p| p[axis] > p.w)
+        })
+    }
+
+    // 屏幕空间 (y 轴向上) 中逆时针为正面
+    fn is_front_facing(t: &Triangle) -> bool {
+        let (a, b, c) = (t.v[0], t.v[1], t.v[2]);
+        (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x) > 0.0
+    }
+
+    fn draw_wireframe(&mut self, screen: &Vec<ScreenTriangle>) {
+        if !self.wireframe {
+            return;
+        }
+        for (_, t, _) in screen {
+            for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+                crate::rasterizer1::Rasterizer::draw_line(&t.v[i].xyz(), &t.v[j].xyz(),
+                                                          self.height, self.width, &mut self.frame_buf);
+            }
+        }
+    }
+
+    // 构造片元着色器输入, ind 为像素在 frame buffer 中的下标, view_pos 为 view space 坐标
+    fn fragment_payload(&self, ind: usize, color: &Vector3<f64>, normal: &Vector3<f64>, tex_coords: &Vector2<f64>,
+                        view_pos: &Vector3<f64>) -> FragmentShaderPayload<'_> {
+        let texture = self.texture.as_ref().map(Rc::new);
+        let mut payload = FragmentShaderPayload::new(color, normal, tex_coords, texture);
+        payload.view_pos = *view_pos;
+        payload.lights = Some(&self.lights);
+        payload.shadows = self.shadow_maps.as_ref();
+        payload.instance_id = self.instance;
+        payload.material_id = self.material;
+        if let Some(instance) = self.instances.get(self.instance) {
+            payload.tint = instance.tint;
+        }
+        if let Some(ao) = &self.ao_buf {
+            payload.ambient_occlusion = ao[ind];
+        }
+        payload
+    }
+
+    // early-Z: 三角形最近的深度不小于所覆盖块的最远深度时整体跳过, 否则逐块检验;
+    // 通过深度测试的像素才插值属性并调用片元着色器, 写入后更新所在块的最远深度
+    fn rasterize_early_z(&mut self, t: &Triangle, view_pos: &Vec<Vector3<f64>>) {
+        let Some(mut hiz) = self.hiz.take() else { return };
+        let (width, height) = (self.width, self.height);
+        let v = &t.v;
+        let Some(rect) = coverage::bounding_rect(v, width as usize, height as usize) else {
+            self.hiz = Some(hiz);
+            return;
+        };
+        let ((x0, x1), (y0, y1)) = rect;
+        let z_min = v.iter().map(|v| v.z).fold(f64::MAX, f64::min);
+        if hiz.occluded_rect(rect, z_min) {
+            self.count(|s| s.triangles_occluded += 1);
+            self.hiz = Some(hiz);
+            return;
+        }
+
+        let ((tx0, ty0), (tx1, ty1)) = (HiZ::tile_of(x0, y0), HiZ::tile_of(x1, y1));
+        for ty in ty0..=ty1 {
+            for tx in tx0..=tx1 {
+                let occluded = hiz.occluded(tx, ty, z_min);
+                self.count(|s| {
+                    s.tiles_tested += 1;
+                    s.tiles_occluded += occluded as u64;
+                });
+                if occluded {
+                    continue;
+                }
+                let ((bx0, bx1), (by0, by1)) = hiz.tile_pixels(tx, ty);
+                let tile = ((bx0.max(x0), bx1.min(x1)), (by0.max(y0), by1.min(y1)));
+                let mut written = false;
+                for (x, y, (a, b, c)) in coverage::pixels(v, tile) {
+                    let z = a * v[0].z + b * v[1].z + c * v[2].z;
+                    let ind = Self::get_index(height, width, x, y);
+                    if !self.depth_test(ind, z) {
+                        continue;
+                    }
+                    written = true;
+                    let (a, b, c) = (a / v[0].w, b / v[1].w, c / v[2].w);
+                    let weight = a + b + c;
+                    let color = Self::interpolate_vec3(a, b, c, t.color[0], t.color[1], t.color[2], weight);
+                    let normal = Self::interpolate_vec3(a, b, c, t.normal[0], t.normal[1], t.normal[2], weight);
+                    let tex_coords = Self::interpolate_vec2(a, b, c, t.tex_coords[0], t.tex_coords[1], t.tex_coords[2], weight);
+                    let position = Self::interpolate_vec3(a, b, c, view_pos[0], view_pos[1], view_pos[2], weight);
+                    self.frame_buf[ind] = self.shade(&self.fragment_payload(ind, &color, &normal, &tex_coords, &position));
+                }
+                if written {
+                    hiz.update(&self.depth_buf, tx, ty);
+                }
+            }
+        }
+        self.hiz = Some(hiz);
+    }
+
+    // 几何 pass: 写入 G-buffer, 属性按透视校正插值; instance 为实例编号
+    fn rasterize_gbuffer(&self, gbuffer: &mut GBuffer, t: &Triangle, view_pos: &Vec<Vector3<f64>>, instance: usize) {
+        let (width, height) = (gbuffer.width as u64, gbuffer.height as u64);
+        let v = &t.v;
+        for (x, y, (a, b, c)) in coverage::coverage(v, width as usize, height as usize) {
+            let z = a * v[0].z + b * v[1].z + c * v[2].z;
+            let ind = Self::get_index(height, width, x, y);
+            let passed = z < gbuffer.depth[ind];
+            self.count(|s| {
+                s.fragments_tested += 1;
+                s.fragments_passed += passed as u64;
+            });
+            if !passed {
+                continue;
+            }
+            let (a, b, c) = (a / v[0].w, b / v[1].w, c / v[2].w);
+            let weight = a + b + c;
+            gbuffer.depth[ind] = z;
+            gbuffer.position[ind] = Self::interpolate_vec3(a, b, c, view_pos[0], view_pos[1], view_pos[2], weight);
+            gbuffer.normal[ind] = Self::interpolate_vec3(a, b, c, t.normal[0], t.normal[1], t.normal[2], weight);
+            gbuffer.tex_coords[ind] = Self::interpolate_vec2(a, b, c, t.tex_coords[0], t.tex_coords[1], t.tex_coords[2], weight);
+            gbuffer.albedo[ind] = Self::interpolate_vec3(a, b, c, t.color[0], t.color[1], t.color[2], weight);
+            gbuffer.material[ind] = t.material_id;
+            gbuffer.instance[ind] = instance;
+        }
+    }
+
+    // 光照 pass: 对 G-buffer 中每个被覆盖的像素调用片元着色器
+    fn shade_gbuffer(&mut self, gbuffer: &GBuffer) {
+        if self.fragment_shader.is_none() {
+            return;
+        }
+        for ind in 0..gbuffer.depth.len() {
+            if !gbuffer.covered(ind) || gbuffer.depth[ind] >= self.depth_buf[ind] {
+                continue;
+            }
+            self.instance = gbuffer.instance[ind];
+            self.material = gbuffer.material[ind];
+            let color = self.shade(&self.fragment_payload(ind, &gbuffer.albedo[ind], &gbuffer.normal[ind],
+                                                          &gbuffer.tex_coords[ind], &gbuffer.position[ind]));
+            self.frame_buf[ind] = color;
+            self.depth_buf[ind] = gbuffer.depth[ind];
+        }
+    }
+
+    // 阴影 pass: 只写深度, 光源位置与着色时一样位于 view space; 所有实例都投射阴影
+    fn render_shadow_maps(&self, triangles: &Vec<Triangle>, settings: ShadowSettings) -> ShadowMaps {
+        let view_space: Vec<[Vector3<f64>; 3]> = self.instances.iter().enumerate()
+            .flat_map(|(k, instance)| {
+                let mv = self.view * instance.model;
+                triangles.iter().map(move |t| {
+                    let t = self.vertex_stage(t, k);
+                    [(mv * t.v[0]).xyz(), (mv * t.v[1]).xyz(), (mv * t.v[2]).xyz()]
+                })
+            })
+            .collect();
+
+        // 场景包围球, 用于确定光源视锥
+        let count = (view_space.len() * 3).max(1) as f64;
+        let center = view_space.iter().flatten().fold(Vector3::zeros(), |acc, p| acc + p) / count;
+        let radius = view_space.iter().flatten().map(|p| (p - center).norm()).fold(1e-3, f64::max);
+
+        let mut maps = vec![];
+        for light in &self.lights {
+            let mut map = ShadowMap::new(light.position, center, radius, settings.resolution);
+            for tri in &view_space {
+                Self::rasterize_shadow_triangle(&mut map, tri);
+            }
+            maps.push(map);
+        }
+        ShadowMaps { maps, settings }
+    }
+
+    fn rasterize_shadow_triangle(map: &mut ShadowMap, tri: &[Vector3<f64>; 3]) {
+        let p: Vec<(f64, f64, f64)> = tri.iter().map(|v| map.project(v)).collect();
+        if p.iter().any(|v| v.2 <= 0.0) {
+            return;
+        }
+        let v = [
+            Vector4::new(p[0].0, p[0].1, p[0].2, 1.0),
+            Vector4::new(p[1].0, p[1].1, p[1].2, 1.0),
+            Vector4::new(p[2].0, p[2].1, p[2].2, 1.0),
+        ];
+        for (x, y, (a, b, c)) in coverage::coverage(&v, map.size, map.size) {
+            // 1/w 在屏幕空间中线性, 插值后得到透视校正的线性深度
+            let depth = 1.0 / (a / v[0].z + b / v[1].z + c / v[2].z);
+            let ind = y * map.size + x;
+            if depth < map.depth[ind] {
+                map.depth[ind] = depth;
+            }
+        }
+    }
+
     fn interpolate_vec3(a: f64, b: f64, c: f64, vert1: Vector3<f64>, vert2: Vector3<f64>, vert3: Vector3<f64>, weight: f64) -> Vector3<f64> {
         (a * vert1 + b * vert2 + c * vert3) / weight
     }
@@ -121,7 +611,7 @@ impl Rasterizer {
     }
 
     fn get_new_tri(t: &Triangle, view: Matrix4<f64>, model: Matrix4<f64>, mvp: Matrix4<f64>,
-                    (width, height): (u64, u64)) -> (Triangle, Vec<Vector3<f64>>) {
+                    (width, height): (u64, u64)) -> Result<(Triangle, Vec<Vector3<f64>>)> {
         let f1 = (50.0 - 0.1) / 2.0; // zfar和znear距离的一半
         let f2 = (50.0 + 0.1) / 2.0; // zfar和znear的中心z坐标
         let mut new_tri = (*t).clone();
@@ -135,7 +625,10 @@ impl Rasterizer {
             vec.y /= vec.w;
             vec.z /= vec.w;
         }
-        let inv_trans = (view * model).try_inverse().unwrap().transpose();
+        // 法线用 model-view 的逆转置变换, 矩阵不可逆 (如某个方向缩放为零) 时无法进行
+        let inv_trans = (view * model).try_inverse()
+            .ok_or_else(|| Error::InvalidParameter(String::from("model-view 矩阵不可逆, 无法变换法线")))?
+            .transpose();
         let n: Vec<Vector4<f64>> = (0..3).map(|i| inv_trans * to_vec4(t.normal[i], Some(0.0))).collect();
 
         // 视口变换得到顶点在屏幕上的坐标, 即screen space
@@ -151,24 +644,35 @@ impl Rasterizer {
             new_tri.set_normal(i, n[i].xyz());
         }
 
-        new_tri.set_color(0, 148.0, 121.0, 92.0);
-        new_tri.set_color(1, 148.0, 121.0, 92.0);
-        new_tri.set_color(2, 148.0, 121.0, 92.0);
+        new_tri.color = [Vector3::new(148.0, 121.0, 92.0) / 255.0; 3];
 
-        (new_tri, view_space_pos)
+        Ok((new_tri, view_space_pos))
+    }
+
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
     }
 
     pub fn frame_buffer(&self) -> &Vec<Vector3<f64>> {
         &self.frame_buf
     }
 
+    /// 屏幕空间深度, 未覆盖的像素为 f64::MAX
+    pub fn depth_buffer(&self) -> &Vec<f64> {
+        &self.depth_buf
+    }
+
 }
 
 fn to_vec4(v3: Vector3<f64>, w: Option<f64>) -> Vector4<f64> {
     Vector4::new(v3.x, v3.y, v3.z, w.unwrap_or(1.0))
 }
 
-fn inside_triangle(x: f64, y: f64, v: &[Vector4<f64>; 3]) -> bool {
+pub fn inside_triangle(x: f64, y: f64, v: &[Vector4<f64>; 3]) -> bool {
     let v = [
         Vector3::new(v[0].x, v[0].y, 1.0),
         Vector3::new(v[1].x, v[1].y, 1.0),
@@ -187,7 +691,7 @@ fn inside_triangle(x: f64, y: f64, v: &[Vector4<f64>; 3]) -> bool {
     }
 }
 
-fn compute_barycentric2d(x: f64, y: f64, v: &[Vector4<f64>; 3]) -> (f64, f64, f64) {
+pub fn compute_barycentric2d(x: f64, y: f64, v: &[Vector4<f64>; 3]) -> (f64, f64, f64) {
     let c1 = (x * (v[1].y - v[2].y) + (v[2].x - v[1].x) * y + v[1].x * v[2].y - v[2].x * v[1].y) / (v[0].x * (v[1].y - v[2].y) + (v[2].x - v[1].x) * v[0].y + v[1].x * v[2].y - v[2].x * v[1].y);
     let c2 = (x * (v[2].y - v[0].y) + (v[0].x - v[2].x) * y + v[2].x * v[0].y - v[0].x * v[2].y) / (v[1].x * (v[2].y - v[0].y) + (v[0].x - v[2].x) * v[1].y + v[2].x * v[0].y - v[0].x * v[2].y);
     let c3 = (x * (v[0].y - v[1].y) + (v[1].x - v[0].x) * y + v[0].x * v[1].y - v[1].x * v[0].y) / (v[2].x * (v[0].y - v[1].y) + (v[1].x - v[0].x) * v[2].y + v[0].x * v[1].y - v[1].x * v[0].y);
@@ -0,0 +1,373 @@
+//! 场景图: 节点带有局部变换与父子关系, 可挂载网格、材质、光源与相机
+//! 世界矩阵自根节点向下传播 (world = parent.world * local), 遍历时每个网格节点发出一次 draw
+
+use std::rc::Rc;
+use nalgebra::Vector4;
+use crate::bounds::Aabb;
+use crate::buffers::{Arena, Handle};
+use crate::camera::Camera;
+use crate::error::Result;
+use crate::instance::Instance;
+pub use crate::mesh::Mesh;
+use crate::rasterizer3::Rasterizer;
+use crate::shader::Light;
+use crate::stats::RenderStats;
+use crate::transform::Transform;
+use crate::utils::{M4f, V3f};
+
+/// 节点的材质, 绘制时作为实例属性传给着色器
+#[derive(Clone, Copy, Debug)]
+pub struct Material {
+    pub tint: V3f,
+    pub material_id: Option<usize>, // 代替三角形自带的材质编号
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material { tint: V3f::repeat(1.0), material_id: None }
+    }
+}
+
+pub type NodeId = Handle<Node>;
+
+pub struct Node {
+    pub name: String,
+    pub transform: Transform, // 相对父节点
+    pub mesh: Option<Rc<Mesh>>,
+    pub material: Material,
+    pub light: Option<Light>,   // 位置在节点的局部空间中
+    pub camera: Option<Camera>, // eye / target / up 在节点的局部空间中
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: M4f, // 由 Scene::update 计算
+}
+
+impl Node {
+    pub fn new(name: &str) -> Self {
+        Node {
+            name: String::from(name),
+            transform: Transform::new(),
+            mesh: None,
+            material: Material::default(),
+            light: None,
+            camera: None,
+            parent: None,
+            children: vec![],
+            world: M4f::identity(),
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_mesh(mut self, mesh: Rc<Mesh>) -> Self {
+        self.mesh = Some(mesh);
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_light(mut self, light: Light) -> Self {
+        self.light = Some(light);
+        self
+    }
+
+    pub fn with_camera(mut self, camera: Camera) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// 最近一次 Scene::update 得到的世界矩阵
+    pub fn world_matrix(&self) -> M4f {
+        self.world
+    }
+}
+
+#[derive(Default)]
+pub struct Scene {
+    nodes: Arena<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    /// 加入节点, parent 为 None 时作为根节点
+    pub fn add(&mut self, mut node: Node, parent: Option<NodeId>) -> Result<NodeId> {
+        node.parent = parent;
+        node.children.clear();
+        if let Some(parent) = parent {
+            self.nodes.try_get(parent)?;
+        }
+        let id = self.nodes.insert(node);
+        match parent {
+            Some(parent) => self.nodes.try_get_mut(parent)?.children.push(id),
+            None => self.roots.push(id),
+        }
+        Ok(id)
+    }
+
+    /// 删除节点及其整个子树
+    pub fn remove(&mut self, id: NodeId) -> Result<()> {
+        let parent = self.nodes.try_get(id)?.parent;
+        match parent.and_then(|p| self.nodes.get_mut(p)) {
+            Some(parent) => parent.children.retain(|&c| c != id),
+            None => self.roots.retain(|&r| r != id),
+        }
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            stack.extend(self.nodes.remove(id)?.children);
+        }
+        Ok(())
+    }
+
+    pub fn node(&self, id: NodeId) -> Result<&Node> {
+        self.nodes.try_get(id)
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Result<&mut Node> {
+        self.nodes.try_get_mut(id)
+    }
+
+    /// 按名字查找第一个节点 (先序)
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        let mut found = None;
+        self.visit(|id, node| {
+            if found.is_none() && node.name == name {
+                found = Some(id);
+            }
+        });
+        found
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// 先序深度优先遍历, 父节点总在子节点之前
+    pub fn visit(&self, mut f: impl FnMut(NodeId, &Node)) {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes.get(id) {
+                f(id, node);
+                stack.extend(node.children.iter().rev());
+            }
+        }
+    }
+
+    /// 自根节点向下计算每个节点的世界矩阵, 修改 transform 或层级后需调用
+    pub fn update(&mut self) {
+        let mut stack: Vec<(NodeId, M4f)> = self.roots.iter().map(|&r| (r, M4f::identity())).collect();
+        while let Some((id, parent_world)) = stack.pop() {
+            if let Some(node) = self.nodes.get_mut(id) {
+                node.world = parent_world * node.transform.matrix();
+                stack.extend(node.children.iter().map(|&c| (c, node.world)));
+            }
+        }
+    }
+
+    /// 节点自身网格在世界空间中的包围盒, 没有网格时为空
+    pub fn node_bounds(&self, id: NodeId) -> Result<Aabb> {
+        let node = self.nodes.try_get(id)?;
+        Ok(node.mesh.as_ref().map_or(Aabb::empty(), |mesh| mesh.bounds.transform(&node.world)))
+    }
+
+    /// 整个子树在世界空间中的包围盒
+    pub fn bounds(&self, id: NodeId) -> Result<Aabb> {
+        let mut aabb = self.node_bounds(id)?;
+        for &child in &self.nodes.try_get(id)?.children {
+            aabb = aabb.union(&self.bounds(child)?);
+        }
+        Ok(aabb)
+    }
+
+    /// 第一个带相机的节点, eye / target / up 按最近一次 update 的世界矩阵变换到世界空间
+    pub fn camera(&self) -> Option<Camera> {
+        let mut camera = None;
+        self.visit(|_, node| {
+            if let (None, Some(c)) = (camera, node.camera) {
+                let point = |p: V3f| (node.world * Vector4::new(p.x, p.y, p.z, 1.0)).xyz();
+                camera = Some(Camera {
+                    eye: point(c.eye),
+                    target: point(c.target),
+                    up: (node.world * Vector4::new(c.up.x, c.up.y, c.up.z, 0.0)).xyz(),
+                    ..c
+                });
+            }
+        });
+        camera
+    }
+
+    /// 场景中的所有光源, 位置变换到 view space (与着色时一致)
+    pub fn lights(&self, view: &M4f) -> Vec<Light> {
+        let mut lights = vec![];
+        self.visit(|_, node| {
+            if let Some(light) = node.light {
+                let p = light.position;
+                let position = (view * node.world * Vector4::new(p.x, p.y, p.z, 1.0)).xyz();
+                lights.push(Light { position, ..light });
+            }
+        });
+        lights
+    }
+
+    /// 更新世界矩阵后, 以 camera 的视图/投影依次绘制每个网格节点, 返回累计的统计; 视锥外的节点与簇被跳过
+    /// 场景中有光源时代替 rasterizer 的光源; 阴影、G-buffer 与 SSAO 在每次 draw 中只包含当前网格
+    pub fn draw(&mut self, r: &mut Rasterizer, camera: &Camera) -> Result<RenderStats> {
+        self.update();
+        let view = camera.view_matrix();
+        r.set_view(view);
+        r.set_projection(camera.projection_matrix());
+        let lights = self.lights(&view);
+        if !lights.is_empty() {
+            r.set_lights(lights);
+        }
+
+        let mut draws: Vec<(Rc<Mesh>, Instance)> = vec![];
+        self.visit(|_, node| {
+            if let Some(mesh) = &node.mesh {
+                let mut instance = Instance::new(node.world).with_tint(node.material.tint);
+                instance.material_id = node.material.material_id;
+                draws.push((mesh.clone(), instance));
+            }
+        });
+
+        let mut stats = RenderStats::default();
+        for (mesh, instance) in draws {
+            stats.accumulate(&r.draw_mesh_instanced(&mesh, &[instance])?);
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use super::*;
+    use crate::triangle::Triangle;
+
+    fn assert_close(a: V3f, b: V3f) {
+        assert!((a - b).abs().max() < 1e-12, "{} != {}", a, b);
+    }
+
+    fn point(m: &M4f, p: V3f) -> V3f {
+        (m * Vector4::new(p.x, p.y, p.z, 1.0)).xyz()
+    }
+
+    // 局部包围盒为 (0, 0, 0) ~ (1, 1, 0)
+    fn mesh() -> Rc<Mesh> {
+        let mut t = Triangle::new();
+        t.v = [Vector4::new(0.0, 0.0, 0.0, 1.0), Vector4::new(1.0, 0.0, 0.0, 1.0), Vector4::new(0.0, 1.0, 0.0, 1.0)];
+        Rc::new(Mesh::new(vec![t]))
+    }
+
+    fn translate(x: f64, y: f64, z: f64) -> Transform {
+        Transform::new().translate(Vector3::new(x, y, z))
+    }
+
+    #[test]
+    fn world_matrix_propagates_through_nested_nodes() {
+        let mut scene = Scene::new();
+        let parent = scene.add(Node::new("parent").with_transform(translate(1.0, 2.0, 3.0)), None).unwrap();
+        let child = scene.add(Node::new("child").with_transform(Transform::new().rotate_z(90.0)), Some(parent)).unwrap();
+        let leaf = scene.add(Node::new("leaf").with_transform(translate(1.0, 0.0, 0.0)), Some(child)).unwrap();
+        scene.update();
+
+        // 子节点先旋转再随父节点平移: (1, 0, 0) -> (0, 1, 0) -> (1, 3, 3)
+        let child_world = scene.node(child).unwrap().world_matrix();
+        assert_close(point(&child_world, Vector3::x()), Vector3::new(1.0, 3.0, 3.0));
+        let leaf_world = scene.node(leaf).unwrap().world_matrix();
+        assert_close(point(&leaf_world, Vector3::zeros()), Vector3::new(1.0, 3.0, 3.0));
+
+        // 修改父节点后需重新 update
+        scene.node_mut(parent).unwrap().transform = Transform::new();
+        scene.update();
+        assert_close(point(&scene.node(leaf).unwrap().world_matrix(), Vector3::zeros()), Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn bounds_cover_the_subtree() {
+        let mut scene = Scene::new();
+        let root = scene.add(Node::new("root").with_transform(translate(10.0, 0.0, 0.0)).with_mesh(mesh()), None).unwrap();
+        let child = scene.add(Node::new("child").with_transform(translate(0.0, 5.0, 0.0)).with_mesh(mesh()), Some(root)).unwrap();
+        let empty = scene.add(Node::new("empty"), Some(root)).unwrap();
+        scene.update();
+
+        let node = scene.node_bounds(root).unwrap();
+        assert_close(node.min, Vector3::new(10.0, 0.0, 0.0));
+        assert_close(node.max, Vector3::new(11.0, 1.0, 0.0));
+        let subtree = scene.bounds(root).unwrap();
+        assert_close(subtree.min, Vector3::new(10.0, 0.0, 0.0));
+        assert_close(subtree.max, Vector3::new(11.0, 6.0, 0.0));
+        let child = scene.bounds(child).unwrap();
+        assert_close(child.min, Vector3::new(10.0, 5.0, 0.0));
+        assert_close(child.max, Vector3::new(11.0, 6.0, 0.0));
+        assert!(scene.bounds(empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn removing_a_node_drops_its_subtree() {
+        let mut scene = Scene::new();
+        let a = scene.add(Node::new("a"), None).unwrap();
+        let b = scene.add(Node::new("b"), Some(a)).unwrap();
+        let c = scene.add(Node::new("c"), Some(b)).unwrap();
+        let d = scene.add(Node::new("d"), Some(a)).unwrap();
+        let e = scene.add(Node::new("e"), None).unwrap();
+
+        let names = |scene: &Scene| {
+            let mut names = vec![];
+            scene.visit(|_, node| names.push(node.name.clone()));
+            names
+        };
+        assert_eq!(names(&scene), ["a", "b", "c", "d", "e"]);
+
+        scene.remove(b).unwrap();
+        assert_eq!(names(&scene), ["a", "d", "e"]);
+        assert_eq!(scene.node(a).unwrap().children(), &[d]);
+        assert!(scene.node(b).is_err() && scene.node(c).is_err());
+        assert!(scene.find("c").is_none());
+        assert!(scene.remove(c).is_err());
+
+        scene.remove(e).unwrap();
+        assert_eq!(scene.roots(), &[a]);
+    }
+
+    #[test]
+    fn camera_and_lights_follow_their_node() {
+        // 父节点平移 (1, 0, 0), 挂载节点绕 y 轴旋转 90 度: 局部 (0, 0, 1) -> (1, 0, 0) -> (2, 0, 0)
+        let mut scene = Scene::new();
+        let parent = scene.add(Node::new("rig").with_transform(translate(1.0, 0.0, 0.0)), None).unwrap();
+        let mut camera = Camera::new(Vector3::new(0.0, 0.0, 1.0), Vector3::zeros());
+        camera.up = Vector3::x();
+        let light = Light { position: Vector3::new(0.0, 0.0, 1.0), intensity: Vector3::repeat(500.0) };
+        let mount = Node::new("mount").with_transform(Transform::new().rotate_y(90.0)).with_camera(camera).with_light(light);
+        scene.add(mount, Some(parent)).unwrap();
+        scene.update();
+
+        let camera = scene.camera().unwrap();
+        assert_close(camera.eye, Vector3::new(2.0, 0.0, 0.0));
+        assert_close(camera.target, Vector3::new(1.0, 0.0, 0.0));
+        // up 是方向, 只旋转不平移
+        assert_close(camera.up, Vector3::new(0.0, 0.0, -1.0));
+
+        let lights = scene.lights(&translate(0.0, 0.0, -5.0).matrix());
+        assert_eq!(lights.len(), 1);
+        assert_close(lights[0].position, Vector3::new(2.0, 0.0, -5.0));
+        assert_close(lights[0].intensity, light.intensity);
+    }
+}
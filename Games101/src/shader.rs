@@ -1,13 +1,42 @@
+//! 着色器的输入 (payload) 与光源; 各着色器函数见 utils
+
 use std::rc::Rc;
 use nalgebra::{Vector2, Vector3};
+use crate::shadow::ShadowMaps;
 use crate::texture::Texture;
 
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Light {
+    pub position: Vector3<f64>,
+    pub intensity: Vector3<f64>,
+}
+
+/// 默认场景中的两个点光源
+pub fn default_lights() -> Vec<Light> {
+    vec![
+        Light {
+            position: Vector3::new(20.0, 20.0, 20.0),
+            intensity: Vector3::new(500.0, 500.0, 500.0),
+        },
+        Light {
+            position: Vector3::new(-20.0, 20.0, 0.0),
+            intensity: Vector3::new(500.0, 500.0, 500.0),
+        },
+    ]
+}
+
 pub struct FragmentShaderPayload<'a> {
     pub view_pos: Vector3<f64>,
     pub color: Vector3<f64>,
     pub normal: Vector3<f64>,
     pub tex_coords: Vector2<f64>,
     pub texture: Option<Rc<&'a Texture>>,
+    pub lights: Option<&'a [Light]>,
+    pub shadows: Option<&'a ShadowMaps>,
+    pub ambient_occlusion: f64, // 环境光可见度, 由 SSAO 给出
+    pub instance_id: usize,     // 实例化绘制时的实例编号, 普通绘制为 0
+    pub tint: Vector3<f64>,     // 实例颜色, 与漫反射系数逐分量相乘
+    pub material_id: usize,
 }
 
 impl<'a> FragmentShaderPayload<'a> {
@@ -21,10 +50,40 @@ impl<'a> FragmentShaderPayload<'a> {
                 None => None,
                 Some(rc) => Some(rc.clone()),
             },
+            lights: None,
+            shadows: None,
+            ambient_occlusion: 1.0,
+            instance_id: 0,
+            tint: Vector3::repeat(1.0),
+            material_id: 0,
+        }
+    }
+
+    /// 当前片元可见的光源, 强度已按阴影可见性衰减
+    pub fn lights(&self) -> Vec<Light> {
+        let lights = match self.lights {
+            None => default_lights(),
+            Some(lights) => lights.to_vec(),
+        };
+        lights.into_iter().enumerate().map(|(i, mut light)| {
+            light.intensity *= self.shadow_visibility(i);
+            light
+        }).collect()
+    }
+
+    /// 第 i 个光源的可见性, 0 为完全处于阴影中
+    pub fn shadow_visibility(&self, i: usize) -> f64 {
+        match self.shadows {
+            None => 1.0,
+            Some(shadows) => shadows.visibility(i, &self.view_pos, &self.normal),
         }
     }
 }
 
+/// 顶点着色器输入, position 为模型空间坐标, 着色器返回变换前的新位置
 pub struct VertexShaderPayload {
     pub position: Vector3<f64>,
-}
\ No newline at end of file
+    pub instance_id: usize,
+    pub tint: Vector3<f64>,
+    pub material_id: usize, // 已应用实例的材质覆盖
+}
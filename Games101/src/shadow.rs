@@ -0,0 +1,160 @@
+//! 阴影贴图: 从每个光源渲染一遍深度, 着色时查询可见性
+
+use nalgebra::Vector4;
+use crate::camera::Camera;
+use crate::utils::{M4f, V3f};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadowFilter {
+    Hard,
+    Pcf,
+    Pcss,
+}
+
+impl ShadowFilter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hard" => Some(ShadowFilter::Hard),
+            "pcf" => Some(ShadowFilter::Pcf),
+            "pcss" => Some(ShadowFilter::Pcss),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowSettings {
+    pub filter: ShadowFilter,
+    pub resolution: usize,
+    pub bias: f64,       // 常数偏移 (光源空间线性深度)
+    pub slope_bias: f64, // 随 n·l 变小而增大的偏移
+    pub pcf_radius: i32, // PCF 采样半径 (texel)
+    pub light_size: f64, // PCSS 光源尺寸 (texel), 决定半影宽度
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            filter: ShadowFilter::Pcf,
+            resolution: 1024,
+            bias: 0.05,
+            slope_bias: 0.2,
+            pcf_radius: 1,
+            light_size: 24.0,
+        }
+    }
+}
+
+/// 单个光源的阴影贴图, depth 中保存光源视角下的线性深度
+pub struct ShadowMap {
+    pub light_view: M4f,
+    pub light_projection: M4f,
+    pub light_pos: V3f,
+    pub size: usize,
+    pub depth: Vec<f64>,
+}
+
+impl ShadowMap {
+    /// 光源看向场景包围球球心, 视锥恰好包住整个包围球
+    pub fn new(light_pos: V3f, center: V3f, radius: f64, size: usize) -> Self {
+        let dist = (center - light_pos).norm().max(radius * 1.01);
+        let half_fov = (radius / dist).asin() * 1.05;
+        let near = (dist - radius).max(0.01);
+        let mut camera = Camera::new(light_pos, center);
+        camera.fov = (half_fov * 2.0).to_degrees();
+        camera.near = near;
+        camera.far = dist + radius;
+        ShadowMap {
+            light_view: camera.view_matrix(),
+            light_projection: camera.projection_matrix(),
+            light_pos,
+            size,
+            depth: vec![f64::MAX; size * size],
+        }
+    }
+
+    /// 返回 (x, y, 线性深度), x/y 为阴影贴图上的像素坐标
+    pub fn project(&self, p: &V3f) -> (f64, f64, f64) {
+        let clip = self.light_projection * self.light_view * Vector4::new(p.x, p.y, p.z, 1.0);
+        let x = 0.5 * self.size as f64 * (clip.x / clip.w + 1.0);
+        let y = 0.5 * self.size as f64 * (clip.y / clip.w + 1.0);
+        (x, y, clip.w)
+    }
+
+    pub fn depth_at(&self, x: i32, y: i32) -> f64 {
+        if x < 0 || y < 0 || x >= self.size as i32 || y >= self.size as i32 {
+            return f64::MAX;
+        }
+        self.depth[y as usize * self.size + x as usize]
+    }
+
+    // 以 (x, y) 为中心的 PCF, 返回被照亮的比例
+    fn pcf(&self, x: f64, y: f64, d: f64, radius: i32) -> f64 {
+        let (cx, cy) = (x as i32, y as i32);
+        let mut lit = 0.0;
+        let mut total = 0.0;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if d <= self.depth_at(cx + dx, cy + dy) {
+                    lit += 1.0;
+                }
+                total += 1.0;
+            }
+        }
+        lit / total
+    }
+
+    pub fn visibility(&self, p: &V3f, n: &V3f, settings: &ShadowSettings) -> f64 {
+        let (x, y, d) = self.project(p);
+        if x < 0.0 || y < 0.0 || x >= self.size as f64 || y >= self.size as f64 {
+            return 1.0;
+        }
+        let l = (self.light_pos - p).normalize();
+        let cos = n.normalize().dot(&l).clamp(0.0, 1.0);
+        let d = d - settings.bias - settings.slope_bias * (1.0 - cos);
+
+        match settings.filter {
+            ShadowFilter::Hard => {
+                if d <= self.depth_at(x as i32, y as i32) { 1.0 } else { 0.0 }
+            }
+            ShadowFilter::Pcf => self.pcf(x, y, d, settings.pcf_radius),
+            ShadowFilter::Pcss => {
+                // 1. 搜索遮挡物平均深度
+                let search = (settings.light_size / 2.0).max(1.0) as i32;
+                let (mut sum, mut count) = (0.0, 0);
+                for dy in -search..=search {
+                    for dx in -search..=search {
+                        let z = self.depth_at(x as i32 + dx, y as i32 + dy);
+                        if z < d {
+                            sum += z;
+                            count += 1;
+                        }
+                    }
+                }
+                if count == 0 {
+                    return 1.0;
+                }
+                let blocker = sum / count as f64;
+                // 2. 由相似三角形估计半影宽度, 3. 以此为半径做 PCF
+                let penumbra = (d - blocker) / blocker * settings.light_size;
+                let radius = (penumbra.round() as i32).clamp(settings.pcf_radius, 16);
+                self.pcf(x, y, d, radius)
+            }
+        }
+    }
+}
+
+/// 所有光源的阴影贴图, 下标与光源列表一一对应
+pub struct ShadowMaps {
+    pub maps: Vec<ShadowMap>,
+    pub settings: ShadowSettings,
+}
+
+impl ShadowMaps {
+    pub fn visibility(&self, light: usize, p: &V3f, n: &V3f) -> f64 {
+        match self.maps.get(light) {
+            None => 1.0,
+            Some(map) => map.visibility(p, n, &self.settings),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! 屏幕空间环境光遮蔽 (SSAO), 输入为 G-buffer 中的 view space 位置与法线
+
+use nalgebra::{Matrix3, Vector3, Vector4};
+use crate::gbuffer::GBuffer;
+use crate::utils::{M4f, V3f};
+
+#[derive(Clone, Copy, Debug)]
+pub struct SsaoSettings {
+    pub samples: usize,
+    pub radius: f64,   // view space 采样半径
+    pub bias: f64,
+    pub strength: f64, // 遮蔽强度, 0 为关闭
+    pub blur: usize,   // 模糊半径 (像素)
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        SsaoSettings {
+            samples: 16,
+            radius: 0.5,
+            bias: 0.025,
+            strength: 1.0,
+            blur: 2,
+        }
+    }
+}
+
+const NOISE_SIZE: usize = 4;
+
+// 固定种子的 xorshift, 保证每次渲染结果一致
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// 法线方向 (+z) 半球内的采样核, 越靠近中心越密
+fn hemisphere_kernel(n: usize, rng: &mut Rng) -> Vec<V3f> {
+    (0..n).map(|i| {
+        let v = Vector3::new(rng.next() * 2.0 - 1.0, rng.next() * 2.0 - 1.0, rng.next())
+            .normalize() * rng.next();
+        let t = i as f64 / n as f64;
+        v * (0.1 + 0.9 * t * t)
+    }).collect()
+}
+
+// 切平面内的随机旋转向量, 在屏幕上按 4x4 平铺
+fn noise_vectors(rng: &mut Rng) -> Vec<V3f> {
+    (0..NOISE_SIZE * NOISE_SIZE)
+        .map(|_| Vector3::new(rng.next() * 2.0 - 1.0, rng.next() * 2.0 - 1.0, 0.0))
+        .collect()
+}
+
+/// 返回逐像素的环境光可见度, 1 为完全不被遮蔽
+pub fn compute_ssao(gbuffer: &GBuffer, projection: &M4f, settings: &SsaoSettings) -> Vec<f64> {
+    let (width, height) = (gbuffer.width, gbuffer.height);
+    let mut rng = Rng(0x2545F4914F6CDD1D);
+    let kernel = hemisphere_kernel(settings.samples.max(1), &mut rng);
+    let noise = noise_vectors(&mut rng);
+
+    let mut ao = vec![1.0; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let ind = (height - 1 - y) * width + x;
+            if !gbuffer.covered(ind) {
+                continue;
+            }
+            let p = gbuffer.position[ind];
+            let n = gbuffer.normal[ind].normalize();
+            let r = noise[(y % NOISE_SIZE) * NOISE_SIZE + x % NOISE_SIZE];
+            let t = (r - n * r.dot(&n)).try_normalize(1e-9).unwrap_or_else(|| n.cross(&Vector3::x()).normalize());
+            let tbn = Matrix3::from_columns(&[t, n.cross(&t), n]);
+
+            let mut occlusion = 0.0;
+            for k in &kernel {
+                let s = p + tbn * k * settings.radius;
+                let clip = projection * Vector4::new(s.x, s.y, s.z, 1.0);
+                // 位于相机平面之后的采样点无法投影到屏幕上
+                if clip.w <= 0.0 {
+                    continue;
+                }
+                let sx = 0.5 * width as f64 * (clip.x / clip.w + 1.0);
+                let sy = 0.5 * height as f64 * (clip.y / clip.w + 1.0);
+                if sx < 0.0 || sy < 0.0 || sx >= width as f64 || sy >= height as f64 {
+                    continue;
+                }
+                let sind = (height - 1 - sy as usize) * width + sx as usize;
+                if !gbuffer.covered(sind) {
+                    continue;
+                }
+                // view space 中 z 越大越靠近相机
+                let scene_z = gbuffer.position[sind].z;
+                let range = (settings.radius / (p.z - scene_z).abs()).min(1.0);
+                if scene_z >= s.z + settings.bias {
+                    occlusion += range;
+                }
+            }
+            ao[ind] = (1.0 - settings.strength * occlusion / kernel.len() as f64).clamp(0.0, 1.0);
+        }
+    }
+    blur(&ao, gbuffer, settings.blur)
+}
+
+// 只在被覆盖的像素间做盒式模糊, 消除旋转噪声留下的图案
+fn blur(ao: &Vec<f64>, gbuffer: &GBuffer, radius: usize) -> Vec<f64> {
+    if radius == 0 {
+        return ao.clone();
+    }
+    let (width, height) = (gbuffer.width as i64, gbuffer.height as i64);
+    let r = radius as i64;
+    let mut out = ao.clone();
+    for row in 0..height {
+        for col in 0..width {
+            let ind = (row * width + col) as usize;
+            if !gbuffer.covered(ind) {
+                continue;
+            }
+            let (mut sum, mut count) = (0.0, 0.0);
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    let (y, x) = (row + dy, col + dx);
+                    if x < 0 || y < 0 || x >= width || y >= height {
+                        continue;
+                    }
+                    let i = (y * width + x) as usize;
+                    if gbuffer.covered(i) {
+                        sum += ao[i];
+                        count += 1.0;
+                    }
+                }
+            }
+            out[ind] = (sum / count).clamp(0.0, 1.0);
+        }
+    }
+    out
+}
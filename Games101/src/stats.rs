@@ -0,0 +1,77 @@
+//! 一次 draw 的统计信息与各阶段耗时, 用于定位 spot 模型上的性能瓶颈
+
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub triangles_submitted: usize,
+    pub triangles_clipped: usize, // 整个三角形位于视锥某一裁剪平面之外
+    pub triangles_culled: usize,  // 背面剔除
+    pub clusters_tested: usize,   // draw_mesh 时参与视锥剔除的簇
+    pub clusters_culled: usize,   // 包围体完全位于视锥外的簇
+    pub triangles_occluded: usize, // early-Z 时被分层深度整体剔除的三角形
+    pub tiles_tested: u64,         // early-Z 时三角形包围盒覆盖的块
+    pub tiles_occluded: u64,       // 其中被分层深度剔除的块
+    pub fragments_tested: u64,    // 通过覆盖测试, 进入深度测试的片元
+    pub fragments_passed: u64,    // 通过深度测试的片元
+    pub fragments_shaded: u64,    // 片元着色器调用次数
+    pub pixels_covered: u64,      // 最终被覆盖的像素数
+
+    pub vertex_time: Duration,   // MVP 与视口变换, 视锥裁剪 (含包围体检验)
+    pub setup_time: Duration,    // 背面剔除, 阴影贴图与 SSAO 等逐帧准备工作
+    pub coverage_time: Duration, // 光栅化与深度测试 (不含着色)
+    pub shading_time: Duration,  // 片元着色器
+}
+
+impl RenderStats {
+    /// 平均每个被覆盖像素的着色次数
+    pub fn overdraw(&self) -> f64 {
+        self.fragments_shaded as f64 / self.pixels_covered.max(1) as f64
+    }
+
+    /// 累加另一次 draw 的计数与耗时; pixels_covered 为整个 frame buffer 的覆盖数, 取后一次的值
+    pub fn accumulate(&mut self, other: &RenderStats) {
+        self.triangles_submitted += other.triangles_submitted;
+        self.triangles_clipped += other.triangles_clipped;
+        self.triangles_culled += other.triangles_culled;
+        self.clusters_tested += other.clusters_tested;
+        self.clusters_culled += other.clusters_culled;
+        self.triangles_occluded += other.triangles_occluded;
+        self.tiles_tested += other.tiles_tested;
+        self.tiles_occluded += other.tiles_occluded;
+        self.fragments_tested += other.fragments_tested;
+        self.fragments_passed += other.fragments_passed;
+        self.fragments_shaded += other.fragments_shaded;
+        self.pixels_covered = other.pixels_covered;
+        self.vertex_time += other.vertex_time;
+        self.setup_time += other.setup_time;
+        self.coverage_time += other.coverage_time;
+        self.shading_time += other.shading_time;
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.vertex_time + self.setup_time + self.coverage_time + self.shading_time
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        writeln!(f, "三角形: 提交 {}, 裁剪 {}, 剔除 {}",
+                 self.triangles_submitted, self.triangles_clipped, self.triangles_culled)?;
+        if self.clusters_tested > 0 {
+            writeln!(f, "簇: 检验 {}, 视锥外 {}", self.clusters_tested, self.clusters_culled)?;
+        }
+        if self.tiles_tested > 0 || self.triangles_occluded > 0 {
+            writeln!(f, "Hi-Z: 遮挡三角形 {}, 块 检验 {}, 遮挡 {}",
+                     self.triangles_occluded, self.tiles_tested, self.tiles_occluded)?;
+        }
+        writeln!(f, "片元: 测试 {}, 通过 {}, 着色 {}, 覆盖像素 {}, overdraw {:.2}",
+                 self.fragments_tested, self.fragments_passed, self.fragments_shaded,
+                 self.pixels_covered, self.overdraw())?;
+        write!(f, "耗时: 顶点 {:.2}ms, setup {:.2}ms, 覆盖 {:.2}ms, 着色 {:.2}ms, 合计 {:.2}ms",
+               ms(self.vertex_time), ms(self.setup_time), ms(self.coverage_time),
+               ms(self.shading_time), ms(self.total_time()))
+    }
+}
@@ -1,23 +1,28 @@
+//! 任务1: 旋转三角形的线框
 #![allow(warnings)]
 
 pub use std::env;
 pub use nalgebra::Vector3;
-pub use opencv::{
-    Result,
-};
+pub use crate::error::Result;
+#[cfg(feature = "opencv")]
 pub use opencv::core::Vector;
-pub use crate::rasterizer1::{Buffer, Rasterizer,Primitive};
+pub use crate::rasterizer1::{Buffer, Rasterizer, Primitive, PosBufId, IndBufId};
 pub use crate::utils::*;
 pub use crate::shader::FragmentShaderPayload;
 pub use crate::texture::Texture;
-use opencv::imgcodecs::imwrite;
+use crate::debug::DebugMode;
+use crate::camera::{Camera, ViewSettings};
+use crate::transform::Transform;
+use crate::image_io;
+#[cfg(feature = "opencv")]
 use opencv::highgui::{imshow, wait_key};
 
-pub fn t1()-> Result<()>{
-    println!("选择任务1");
-    let mut angle = 0.0;
-    let mut r = Rasterizer::new(700, 700);
-    let eye_pos = Vector3::new(0.0, 0.0, 5.0);
+/// 默认的相机位置
+pub const EYE: V3f = Vector3::new(0.0, 0.0, 5.0);
+
+/// 任务1的场景: 一个三角形
+pub fn setup(method: &str, view: &ViewSettings) -> (Rasterizer, PosBufId, IndBufId) {
+    let mut r = Rasterizer::new(view.width as u64, view.height as u64);
     let pos = vec![Vector3::new(2.0, 0.0, -2.0),
                    Vector3::new(0.0, 2.0, -2.0),
                    Vector3::new(-2.0, 0.0, -2.0)];
@@ -26,27 +31,57 @@ pub fn t1()-> Result<()>{
     let pos_id = r.load_position(&pos);
     let ind_id = r.load_indices(&ind);
 
+    r.set_debug_mode(DebugMode::from_name(method));
+    (r, pos_id, ind_id)
+}
+
+/// 离线渲染一帧, 不打开窗口
+pub fn render(method: &str, model: M4f, view: &ViewSettings) -> Result<Vec<V3f>> {
+    let (mut r, pos_id, ind_id) = setup(method, view);
+    let camera = view.camera(EYE)?;
+    r.clear(Buffer::Both);
+    r.set_model(model);
+    r.set_view(camera.view_matrix());
+    r.set_projection(camera.projection_matrix());
+    r.draw(pos_id, ind_id, Primitive::Triangle)?;
+    Ok(r.frame_buffer().clone())
+}
+
+/// 不打开窗口, 渲染一帧写入文件
+pub fn save(filename: &str, method: &str, transform: &Transform, view: &ViewSettings) -> Result<()> {
+    image_io::save_frame(filename, &render(method, transform.matrix(), view)?, view.width, view.height)
+}
+
+#[cfg(feature = "opencv")]
+pub fn t1(method: String, transform: Transform, view: ViewSettings)-> Result<()>{
+    println!("选择任务1");
+    let mut angle = 0.0;
+    let (mut r, pos_id, ind_id) = setup(&method, &view);
+    let mut camera = view.camera(EYE)?;
+
     let mut k = 0;
     let mut frame_count = 0;
 
     while k != 27 {
         r.clear(Buffer::Both);
-        r.set_model(get_model_matrix(angle,1.0));
-        r.set_view(get_view_matrix(eye_pos));
-        r.set_projection(get_projection_matrix(45.0, 1.0, 0.1, 50.0));
-        r.draw_triangle(pos_id, ind_id, Primitive::Triangle);
+        r.set_model(Transform::new().rotate_z(angle).then(&transform).matrix());
+        r.set_view(camera.view_matrix());
+        r.set_projection(camera.projection_matrix());
+        r.draw(pos_id, ind_id, Primitive::Triangle)?;
 
         let frame_buffer = r.frame_buffer();
-        let image = frame_buffer2cv_mat(frame_buffer);
-        imshow("image", &image).unwrap();
+        let image = frame_buffer2cv_mat(frame_buffer, view.width, view.height)?;
+        imshow("image", &image)?;
 
-        k = wait_key(80).unwrap();
+        k = wait_key(80)?;
         println!("frame count: {}", frame_count);
         if k == 'a' as i32 {
             angle += 10.0;
         } else if k == 'd' as i32 {
             angle -= 10.0;
-        } 
+        } else {
+            camera.handle_key(k);
+        }
         frame_count += 1;
     }
     Ok(())
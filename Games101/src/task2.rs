@@ -1,20 +1,27 @@
+//! 任务2: 三个相互遮挡的纯色三角形
 #![allow(warnings)]
 pub use std::env;
 pub use nalgebra::Vector3;
-pub use opencv::{
-    Result,
-};
+pub use crate::error::Result;
+#[cfg(feature = "opencv")]
 pub use opencv::core::Vector;
-pub use crate::rasterizer2::{Buffer, Rasterizer,Primitive};
+pub use crate::rasterizer2::{Attribute, Buffer, Rasterizer, Primitive, VaoId};
 pub use crate::utils::*;
 pub use crate::shader::FragmentShaderPayload;
 pub use crate::texture::Texture;
-use opencv::imgcodecs::imwrite;
+use crate::debug::DebugMode;
+use crate::camera::{Camera, ViewSettings};
+use crate::transform::Transform;
+use crate::image_io;
+#[cfg(feature = "opencv")]
 use opencv::highgui::{imshow, wait_key};
-pub fn t2() -> Result<()>{
-    println!("选择任务2");
-    let mut r = Rasterizer::new(700, 700);
-    let eye_pos = Vector3::new(0.0, 0.0, 5.0);
+
+/// 默认的相机位置
+pub const EYE: V3f = Vector3::new(0.0, 0.0, 5.0);
+
+/// 任务2的场景: 三个相互遮挡的三角形
+pub fn setup(method: &str, view: &ViewSettings) -> Result<(Rasterizer, VaoId)> {
+    let mut r = Rasterizer::new(view.width as u64, view.height as u64);
     let pos = vec![Vector3::new(2.0, 0.0, -2.0),
                    Vector3::new(0.0, 2.0, -2.0),
                    Vector3::new(-2.0, 0.0, -2.0),
@@ -34,24 +41,56 @@ pub fn t2() -> Result<()>{
                     Vector3::new(238.0, 185.0, 217.0),
                     Vector3::new(238.0, 185.0, 217.0),
                     Vector3::new(238.0, 185.0, 217.0)];
-    let pos_id = r.load_position(&pos);
+    let vao = r.create_vertex_array();
+    let pos_id = r.load_vec3(&pos);
+    let col_id = r.load_vec3(&cols);
     let ind_id = r.load_indices(&ind);
-    let col_id = r.load_colors(&cols);
+    r.bind_attribute(vao, Attribute::Position, pos_id)?;
+    r.bind_attribute(vao, Attribute::Color, col_id)?;
+    r.bind_indices(vao, ind_id)?;
+    r.set_debug_mode(DebugMode::from_name(method));
+    Ok((r, vao))
+}
+
+/// 离线渲染一帧, 不打开窗口
+pub fn render(method: &str, model: M4f, view: &ViewSettings) -> Result<Vec<V3f>> {
+    let (mut r, vao) = setup(method, view)?;
+    let camera = view.camera(EYE)?;
+    r.clear(Buffer::Both);
+    r.set_model(model);
+    r.set_view(camera.view_matrix());
+    r.set_projection(camera.projection_matrix());
+    r.draw_elements(vao, Primitive::Triangle)?;
+    Ok(r.frame_buffer().clone())
+}
+
+/// 不打开窗口, 渲染一帧写入文件
+pub fn save(filename: &str, method: &str, transform: &Transform, view: &ViewSettings) -> Result<()> {
+    image_io::save_frame(filename, &render(method, transform.matrix(), view)?, view.width, view.height)
+}
+
+#[cfg(feature = "opencv")]
+pub fn t2(method: String, transform: Transform, view: ViewSettings) -> Result<()>{
+    println!("选择任务2");
+    let (mut r, vao) = setup(&method, &view)?;
+    let mut camera = view.camera(EYE)?;
+
     let mut k = 0;
     let mut frame_count = 0;
 
     while k != 27 {
         r.clear(Buffer::Both);
-        r.set_model(get_model_matrix(0.0,1.0));
-        r.set_view(get_view_matrix(eye_pos));
-        r.set_projection(get_projection_matrix(45.0, 1.0, 0.1, 50.0));
-        r.draw(pos_id, ind_id, col_id, Primitive::Triangle);
+        r.set_model(transform.matrix());
+        r.set_view(camera.view_matrix());
+        r.set_projection(camera.projection_matrix());
+        r.draw_elements(vao, Primitive::Triangle)?;
 
         let frame_buffer = r.frame_buffer();
-        let image = frame_buffer2cv_mat(frame_buffer);
+        let image = frame_buffer2cv_mat(frame_buffer, view.width, view.height)?;
 
         imshow("image", &image)?;
-        k = wait_key(2000).unwrap();
+        k = wait_key(2000)?;
+        camera.handle_key(k);
         println!("frame count: {}", frame_count);
         frame_count += 1;
     }
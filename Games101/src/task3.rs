@@ -1,51 +1,168 @@
+//! 任务3: spot 模型, 可切换 normal / phong / texture / bump / displacement 着色器
 #![allow(warnings)]
 pub use std::env;
+use std::path::Path;
 pub use nalgebra::Vector3;
-pub use opencv::{
-    Result,
-};
-pub use opencv::core::Vector;
+pub use crate::error::Result;
 pub use crate::rasterizer3::{Buffer, Rasterizer};
 pub use crate::utils::*;
 pub use crate::shader::FragmentShaderPayload;
 pub use crate::texture::Texture;
+use crate::color::{ColorSpace, OutputTransform};
+use crate::shadow::ShadowSettings;
+use crate::ssao::SsaoSettings;
+use crate::color::Transfer;
+use crate::debug::DebugMode;
+use crate::camera::{Camera, ViewSettings};
+use crate::transform::Transform;
+use crate::animation::{self, Animation};
+use crate::hdr;
+use crate::image_io;
+use crate::mesh::Mesh;
+use crate::instance;
+use crate::stats::RenderStats;
 
-pub fn t3(filename:String,method:String)-> Result<()>{
+/// 默认的模型文件, 纹理与高度图在同一目录下
+pub const MODEL: &str = "./models/spot/spot_triangulated_good.obj";
+/// 默认的相机位置
+pub const EYE: V3f = Vector3::new(0.0, 0.0, 10.0);
+/// 实例化绘制时相邻模型的间距
+pub const GRID_SPACING: f64 = 3.0;
+
+/// task3 的可选渲染参数
+#[derive(Default)]
+pub struct T3Options {
+    pub output: OutputTransform,
+    pub view: ViewSettings,
+    pub model: Option<String>,   // obj 文件, 默认为 MODEL
+    pub texture: Option<String>, // 代替模型目录下的颜色贴图 / 高度图
+    pub shadow: Option<ShadowSettings>,
+    pub deferred: bool,
+    pub ssao: Option<SsaoSettings>,
+    pub gbuffer: Option<String>, // G-buffer 导出文件名前缀
+    pub transform: Option<Transform>, // 默认为放大 2.5 倍后绕 y 轴旋转 140 度
+    pub animation: Option<Animation>,
+    pub stats: bool, // 打印 RenderStats
+    pub hdr: Option<String>,   // 浮点 frame buffer 导出路径 (.exr / .pfm / .hdr)
+    pub depth: Option<String>, // 深度缓冲导出路径
+    pub instances: usize, // 大于 1 时把模型在 xz 平面上排成网格, 以实例化方式绘制
+    pub early_z: bool,    // 先做深度测试再着色, 并用分层深度剔除被遮挡的三角形与块
+}
+
+impl T3Options {
+    pub fn model_file(&self) -> &str {
+        self.model.as_deref().unwrap_or(MODEL)
+    }
+
+    /// 模型所在目录 (以 / 结尾), 用于查找默认纹理
+    pub fn model_dir(&self) -> String {
+        match Path::new(self.model_file()).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => format!("{}/", dir.display()),
+            _ => String::from("./"),
+        }
+    }
+
+    /// 按 method 加载着色器与纹理; 不需要纹理的 shader 仍加载模型目录下的高度图 (如果有), 供 mip-level 调试模式使用
+    pub fn load_shader(&self, r: &mut Rasterizer, method: &str) -> Result<()> {
+        let obj_path = self.model_dir();
+        let (shader, tex) = choose_shader_texture(method, &obj_path, self.texture.as_deref())?;
+        r.set_fragment_shader(shader);
+        let hmap = obj_path + "hmap.jpg";
+        match tex {
+            Some(tex) => r.set_texture(tex),
+            None if Path::new(&hmap).exists() => r.set_texture(Texture::with_color_space(&hmap, ColorSpace::Linear)?),
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// 按 method 与 opts 配置好 rasterizer, 返回 (rasterizer, 网格, 相机, 模型变换, 输出变换)
+pub fn setup(method: &str, opts: &T3Options) -> Result<(Rasterizer, Mesh, Camera, Transform, OutputTransform)> {
+    let mesh = Mesh::load(opts.model_file())?;
+    let mut r = Rasterizer::new(opts.view.width as u64, opts.view.height as u64);
+    let output = set_method(&mut r, method, opts)?;
+
+    let camera = opts.view.camera(EYE)?;
+    r.set_vertex_shader(vertex_shader);
+    r.set_shadows(opts.shadow);
+    r.set_ssao(opts.ssao);
+    r.set_deferred(opts.deferred || opts.gbuffer.is_some());
+    r.set_early_z(opts.early_z);
+
+    let model = opts.transform.clone()
+        .unwrap_or_else(|| Transform::new().uniform_scale(2.5).rotate_y(140.0));
+    Ok((r, mesh, camera, model, output))
+}
+
+/// 切换着色器或调试模式, 返回与之对应的输出变换
+pub fn set_method(r: &mut Rasterizer, method: &str, opts: &T3Options) -> Result<OutputTransform> {
+    opts.load_shader(r, method)?;
+    let debug = DebugMode::from_name(method);
+    r.set_debug_mode(debug);
+    let mut output = opts.output;
+    if debug.is_some() {
+        output.transfer = Transfer::Linear; // 调试颜色直接用于显示, 不再编码
+    }
+    Ok(output)
+}
+
+/// 按 opts.instances 绘制一个或一组模型, model 为每个模型自身的变换
+pub fn draw(r: &mut Rasterizer, mesh: &Mesh, model: &Transform, opts: &T3Options) -> Result<RenderStats> {
+    r.set_model(model.matrix());
+    if opts.instances > 1 {
+        r.draw_mesh_instanced(mesh, &instance::grid(opts.instances, GRID_SPACING, model))
+    } else {
+        r.draw_mesh(mesh)
+    }
+}
+
+/// 离线渲染一帧, 返回经过输出变换的颜色 (0~255)
+pub fn render(method: &str, opts: &T3Options) -> Result<Vec<V3f>> {
+    let (mut r, mesh, camera, model, output) = setup(method, opts)?;
+    r.clear(Buffer::Both);
+    r.set_view(camera.view_matrix());
+    r.set_projection(camera.projection_matrix());
+    draw(&mut r, &mesh, &model, opts)?;
+    Ok(output.apply(r.frame_buffer(), r.width() as usize))
+}
+
+pub fn t3(filename:String,method:String,opts:T3Options)-> Result<()>{
     println!("选择任务3");
-    let obj_file = "./models/spot/spot_triangulated_good.obj";
-    let triangles = load_triangles(&obj_file);
-    let angle = 140.0;
-    let mut r = Rasterizer::new(700, 700);
-    let obj_path = "./models/spot/".to_owned();
-    let texture_path = "hmap.jpg".to_owned();
-    let mut tex = Texture::new(&(obj_path.clone() + &texture_path));
-    let mut active_shader: fn(&FragmentShaderPayload) -> Vector3<f64> = normal_fragment_shader; // 默认为<normal shader>
     let ags: Vec<String> = env::args().collect();
     println!("arg len is {}",ags.len());
-    let (shader, t) =
-        choose_shader_texture(&method, &obj_path);
-    active_shader = shader;
-    if let Some(tx) = t {
-        tex = tx;
+    // 导出路径先检查, 免得写出 png 之后才发现格式不对
+    for path in opts.hdr.iter().chain(&opts.depth) {
+        hdr::check_extension(path)?;
     }
-    r.set_texture(tex);
-
-    let eye_pos = Vector3::new(0.0, 0.0, 10.0);
-    r.set_vertex_shader(vertex_shader);
-    r.set_fragment_shader(active_shader);
+    let (mut r, mesh, camera, model, output) = setup(&method, &opts)?;
 
+    if let Some(anim) = &opts.animation {
+        return animation::render(&mut r, &mesh, camera, &model, &output, anim, &filename);
+    }
 
     r.clear(Buffer::Both);
-    r.set_model(get_model_matrix_lab3(angle));
-    r.set_view(get_view_matrix(eye_pos));
-    r.set_projection(get_projection_matrix(45.0, 1.0, 0.1, 50.0));
+    r.set_view(camera.view_matrix());
+    r.set_projection(camera.projection_matrix());
+
+    let stats = draw(&mut r, &mesh, &model, &opts)?;
+    if opts.stats {
+        println!("{}", stats);
+    }
 
-    r.draw(&triangles);
+    let (width, height) = (opts.view.width, opts.view.height);
+    image_io::save_frame(&filename, &output.apply(r.frame_buffer(), width), width, height)?;
 
-    let image = frame_buffer2cv_mat(r.frame_buffer());
-    let v: Vector<i32> = Default::default();
+    if let Some(path) = &opts.hdr {
+        hdr::write_color(path, r.frame_buffer(), width, height)?;
+    }
+    if let Some(path) = &opts.depth {
+        hdr::write_depth(path, r.depth_buffer(), width, height)?;
+    }
 
-    opencv::imgcodecs::imwrite(&filename, &image, &v).unwrap();
+    if let (Some(prefix), Some(gbuffer)) = (&opts.gbuffer, r.gbuffer()) {
+        gbuffer.export(prefix)?;
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,42 +1,60 @@
+//! 纹理: 加载 8 位图像, 采样时解码到线性空间
 #![allow(warnings)]
 use nalgebra::{Vector3};
 
-use opencv::core::{MatTraitConst, VecN};
-use opencv::imgcodecs::{imread, IMREAD_COLOR};
+use crate::color::{ColorSpace, decode_lut};
+use crate::error::Result;
+use crate::image_io::{self, RgbImage};
 
 pub struct Texture {
-    pub img_data: opencv::core::Mat,
+    pub img_data: RgbImage,
     pub width: usize,
     pub height: usize,
+    pub color_space: ColorSpace,
+    lut: [f64; 256],
 }
 
 impl Texture {
-    pub fn new(name: &str) -> Self {
-        let img_data = imread(name, IMREAD_COLOR).expect("Image reading error!");
-        let width = img_data.cols() as usize;
-        let height = img_data.rows() as usize;
-        Texture {
+    /// 颜色贴图默认按 sRGB 处理
+    pub fn new(name: &str) -> Result<Self> {
+        Self::with_color_space(name, ColorSpace::Srgb)
+    }
+
+    /// 高度图、法线图等数据贴图应使用 ColorSpace::Linear
+    pub fn with_color_space(name: &str, color_space: ColorSpace) -> Result<Self> {
+        let img_data = image_io::load(name)?;
+        let width = img_data.width;
+        let height = img_data.height;
+        Ok(Texture {
             img_data,
             width,
             height,
-        }
+            color_space,
+            lut: decode_lut(color_space),
+        })
     }
 
+    /// 返回线性空间的颜色, 仍为 0~255 标度
     pub fn get_color(&self, mut u: f64, mut v: f64) -> Vector3<f64> {
         if u < 0.0 { u = 0.0; }
         if u > 1.0 { u = 1.0; }
         if v < 0.0 { v = 0.0; }
         if v > 1.0 { v = 1.0; }
 
-        let u_img = u * self.width as f64;
-        let v_img = (1.0 - v) * self.height as f64;
-        let color: &VecN<u8, 3> = self.img_data.at_2d(v_img as i32, u_img as i32).unwrap();
+        let u_img = (u * self.width as f64) as usize;
+        let v_img = ((1.0 - v) * self.height as f64) as usize;
+        let color = self.img_data.pixel(u_img.min(self.width - 1), v_img.min(self.height - 1));
+
+        Vector3::new(self.decode(color[0]), self.decode(color[1]), self.decode(color[2]))
+    }
 
-        Vector3::new(color[2] as f64, color[1] as f64, color[0] as f64)
+    fn decode(&self, c: u8) -> f64 {
+        self.lut[c as usize] * 255.0
     }
 
     pub fn get_color_bilinear(&self, mut u: f64, mut v: f64) -> Vector3<f64> {
         // 在此实现双线性插值函数, 并替换掉get_color
+        // 注意: 应在线性空间中插值 (对 get_color 的结果插值即可)
 
         Vector3::new(0.0, 0.0, 0.0)
     }
@@ -0,0 +1,128 @@
+//! 图元拓扑: 把扁平的下标缓冲按拓扑展开为点 / 线段 / 三角形
+//! 下标为 PRIMITIVE_RESTART 时结束当前的 strip / fan, 从下一个下标重新开始
+
+/// 下标缓冲中的重启标记
+pub const PRIMITIVE_RESTART: usize = usize::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    Point,
+    Line,
+    LineStrip,
+    Triangle,
+    TriangleStrip,
+    TriangleFan,
+}
+
+/// 装配得到的一个图元, 保存其顶点下标
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Element {
+    Point(usize),
+    Line([usize; 2]),
+    Triangle([usize; 3]),
+}
+
+impl Primitive {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "points" => Some(Primitive::Point),
+            "lines" => Some(Primitive::Line),
+            "line-strip" => Some(Primitive::LineStrip),
+            "triangles" => Some(Primitive::Triangle),
+            "triangle-strip" => Some(Primitive::TriangleStrip),
+            "triangle-fan" => Some(Primitive::TriangleFan),
+            _ => None,
+        }
+    }
+}
+
+/// 三角形列表的下标展开为扁平的下标缓冲
+pub fn flatten(indices: &[nalgebra::Vector3<usize>]) -> Vec<usize> {
+    indices.iter().flat_map(|i| [i[0], i[1], i[2]]).collect()
+}
+
+/// 按拓扑装配图元; 列表末尾不足一个图元的下标被忽略
+/// strip 中奇数个三角形交换前两个顶点, 使所有三角形的环绕方向与第一个一致
+pub fn assemble(indices: &[usize], primitive: Primitive) -> Vec<Element> {
+    let mut elements = Vec::new();
+    for run in indices.split(|&i| i == PRIMITIVE_RESTART) {
+        match primitive {
+            Primitive::Point => elements.extend(run.iter().map(|&i| Element::Point(i))),
+            Primitive::Line => elements.extend(run.chunks_exact(2).map(|l| Element::Line([l[0], l[1]]))),
+            Primitive::LineStrip => elements.extend(run.windows(2).map(|l| Element::Line([l[0], l[1]]))),
+            Primitive::Triangle => elements.extend(run.chunks_exact(3).map(|t| Element::Triangle([t[0], t[1], t[2]]))),
+            Primitive::TriangleStrip => elements.extend(run.windows(3).enumerate().map(|(k, t)| {
+                if k % 2 == 0 {
+                    Element::Triangle([t[0], t[1], t[2]])
+                } else {
+                    Element::Triangle([t[1], t[0], t[2]])
+                }
+            })),
+            Primitive::TriangleFan => {
+                if let Some((&center, rest)) = run.split_first() {
+                    elements.extend(rest.windows(2).map(|t| Element::Triangle([center, t[0], t[1]])));
+                }
+            }
+        }
+    }
+    elements
+}
+
+/// 第一个越界的下标 (不含重启标记)
+pub fn out_of_range(indices: &[usize], vertex_count: usize) -> Option<usize> {
+    indices.iter().copied().find(|&i| i != PRIMITIVE_RESTART && i >= vertex_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const R: usize = PRIMITIVE_RESTART;
+
+    fn triangles(elements: &[Element]) -> Vec<[usize; 3]> {
+        elements.iter().map(|e| match e {
+            Element::Triangle(t) => *t,
+            _ => panic!("expected a triangle, got {:?}", e),
+        }).collect()
+    }
+
+    #[test]
+    fn strip_keeps_winding() {
+        // 两行顶点交替排列成一条带, 偶数号在下, 奇数号在上
+        let p: Vec<(f64, f64)> = (0..6).map(|i| ((i / 2) as f64, (i % 2) as f64)).collect();
+        let area = |t: [usize; 3]| {
+            let (a, b, c) = (p[t[0]], p[t[1]], p[t[2]]);
+            (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+        };
+        let tris = triangles(&assemble(&[0, 1, 2, 3, 4, 5], Primitive::TriangleStrip));
+        assert_eq!(tris, vec![[0, 1, 2], [2, 1, 3], [2, 3, 4], [4, 3, 5]]);
+        let first = area(tris[0]).signum();
+        assert!(tris.iter().all(|&t| area(t).signum() == first));
+    }
+
+    #[test]
+    fn fan_is_anchored_on_first_index() {
+        let tris = triangles(&assemble(&[7, 1, 2, 3, 4], Primitive::TriangleFan));
+        assert_eq!(tris, vec![[7, 1, 2], [7, 2, 3], [7, 3, 4]]);
+    }
+
+    #[test]
+    fn restart_splits_strips_and_fans() {
+        let strip = triangles(&assemble(&[0, 1, 2, 3, R, 4, 5, 6], Primitive::TriangleStrip));
+        assert_eq!(strip, vec![[0, 1, 2], [2, 1, 3], [4, 5, 6]]);
+        let fan = triangles(&assemble(&[0, 1, 2, 3, R, 4, 5, 6], Primitive::TriangleFan));
+        assert_eq!(fan, vec![[0, 1, 2], [0, 2, 3], [4, 5, 6]]);
+        let lines = assemble(&[0, 1, 2, R, 3, 4], Primitive::LineStrip);
+        assert_eq!(lines, vec![Element::Line([0, 1]), Element::Line([1, 2]), Element::Line([3, 4])]);
+    }
+
+    #[test]
+    fn incomplete_primitives_are_dropped() {
+        assert_eq!(triangles(&assemble(&[0, 1, 2, 3, 4], Primitive::Triangle)), vec![[0, 1, 2]]);
+        assert_eq!(assemble(&[0, 1, 2], Primitive::Line), vec![Element::Line([0, 1])]);
+        // 重启标记前后不足一个图元的部分也被忽略
+        assert_eq!(triangles(&assemble(&[0, 1, R, 2, 3, 4, R, 5], Primitive::TriangleStrip)), vec![[2, 3, 4]]);
+        assert!(assemble(&[0, 1], Primitive::TriangleFan).is_empty());
+        assert!(assemble(&[R, R], Primitive::Triangle).is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! 模型变换: 按调用顺序组合平移/缩放/旋转, 先调用的先作用在物体上
+//! 例: Transform::new().scale(2.5).rotate_y(140.0).translate(..) 即 T * R * S
+
+use std::str::FromStr;
+use nalgebra::{Matrix3, Matrix4, Quaternion, UnitQuaternion, Vector3};
+use crate::error::{Error, Result};
+use crate::utils::{M4f, V3f};
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Translate(V3f),
+    Scale(V3f),
+    Rotate(Matrix3<f64>),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Transform {
+    ops: Vec<Op>,
+}
+
+/// Rodrigues 旋转公式: R = cos(t) I + (1 - cos(t)) k k^T + sin(t) `[k]x`
+pub fn rodrigues(axis: &V3f, angle: f64) -> Matrix3<f64> {
+    let k = axis.normalize();
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let cross = Matrix3::new(
+        0.0, -k.z, k.y,
+        k.z, 0.0, -k.x,
+        -k.y, k.x, 0.0,
+    );
+    Matrix3::identity() * cos + k * k.transpose() * (1.0 - cos) + cross * sin
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Transform { ops: vec![] }
+    }
+
+    pub fn translate(mut self, t: V3f) -> Self {
+        self.ops.push(Op::Translate(t));
+        self
+    }
+
+    pub fn scale(mut self, s: V3f) -> Self {
+        self.ops.push(Op::Scale(s));
+        self
+    }
+
+    pub fn uniform_scale(self, s: f64) -> Self {
+        self.scale(Vector3::repeat(s))
+    }
+
+    fn push_rotation(mut self, r: Matrix3<f64>) -> Self {
+        self.ops.push(Op::Rotate(r));
+        self
+    }
+
+    /// 绕任意轴旋转, 角度制; 轴为零向量时无法确定方向, 返回错误
+    pub fn rotate(self, axis: V3f, angle: f64) -> Result<Self> {
+        if axis.norm() <= f64::EPSILON {
+            return Err(Error::InvalidParameter(String::from("旋转轴不能为零向量")));
+        }
+        Ok(self.push_rotation(rodrigues(&axis, angle)))
+    }
+
+    pub fn rotate_x(self, angle: f64) -> Self {
+        self.push_rotation(rodrigues(&Vector3::x(), angle))
+    }
+
+    pub fn rotate_y(self, angle: f64) -> Self {
+        self.push_rotation(rodrigues(&Vector3::y(), angle))
+    }
+
+    pub fn rotate_z(self, angle: f64) -> Self {
+        self.push_rotation(rodrigues(&Vector3::z(), angle))
+    }
+
+    /// 四元数 (w, x, y, z), 不要求已归一化, 但不能为零
+    pub fn rotate_quaternion(self, w: f64, x: f64, y: f64, z: f64) -> Result<Self> {
+        let q = Quaternion::new(w, x, y, z);
+        if q.norm() <= f64::EPSILON {
+            return Err(Error::InvalidParameter(String::from("旋转四元数不能为零")));
+        }
+        let q = UnitQuaternion::from_quaternion(q);
+        Ok(self.push_rotation(q.to_rotation_matrix().into_inner()))
+    }
+
+    /// 欧拉角 (角度制), 依次绕固定的 x, y, z 轴旋转
+    pub fn rotate_euler(self, x: f64, y: f64, z: f64) -> Self {
+        self.rotate_x(x).rotate_y(y).rotate_z(z)
+    }
+
+    /// 先作用 self, 再作用 other
+    pub fn then(mut self, other: &Transform) -> Self {
+        self.ops.extend(other.ops.iter().cloned());
+        self
+    }
+
+    pub fn matrix(&self) -> M4f {
+        self.ops.iter().fold(Matrix4::identity(), |m, op| {
+            let step = match op {
+                Op::Translate(t) => Matrix4::new_translation(t),
+                Op::Scale(s) => Matrix4::new_nonuniform_scaling(s),
+                Op::Rotate(r) => r.to_homogeneous(),
+            };
+            step * m
+        })
+    }
+}
+
+// 文本形式, 用于命令行和场景文件, 各项按书写顺序作用:
+//   t=x,y,z          平移
+//   s=k 或 s=x,y,z   缩放
+//   r=ax,ay,az,deg   绕任意轴旋转
+//   q=w,x,y,z        四元数
+//   e=x,y,z          欧拉角
+// 例: "s=2.5;r=0,1,0,140;t=0,0,-1"
+impl FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut transform = Transform::new();
+        for item in s.split(';').map(str::trim).filter(|item| !item.is_empty()) {
+            let invalid = |reason: &str| format!("变换项 `{}` 不合法: {}", item, reason);
+            let (key, value) = item.split_once('=').ok_or_else(|| invalid("缺少 `=`"))?;
+            let v: Vec<f64> = value.split(',')
+                .map(|x| x.trim().parse::<f64>())
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|e| invalid(&e.to_string()))?;
+            if v.iter().any(|x| !x.is_finite()) {
+                return Err(invalid("数值必须是有限的"));
+            }
+            // 缩放为零时模型矩阵不可逆, 法线变换无法进行
+            if key.trim() == "s" && v.contains(&0.0) {
+                return Err(invalid("缩放系数不能为零"));
+            }
+            transform = match (key.trim(), v.as_slice()) {
+                ("t", &[x, y, z]) => transform.translate(Vector3::new(x, y, z)),
+                ("s", &[k]) => transform.uniform_scale(k),
+                ("s", &[x, y, z]) => transform.scale(Vector3::new(x, y, z)),
+                ("r", &[x, y, z, angle]) => transform.rotate(Vector3::new(x, y, z), angle)
+                    .map_err(|e| invalid(&e.to_string()))?,
+                ("q", &[w, x, y, z]) => transform.rotate_quaternion(w, x, y, z)
+                    .map_err(|e| invalid(&e.to_string()))?,
+                ("e", &[x, y, z]) => transform.rotate_euler(x, y, z),
+                _ => return Err(invalid("未知的键或分量个数不对")),
+            };
+        }
+        Ok(transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Matrix3<f64>, b: &Matrix3<f64>) {
+        assert!((a - b).abs().max() < 1e-12, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn rodrigues_matches_axis_rotations() {
+        assert_close(&rodrigues(&Vector3::z(), 90.0), &Matrix3::new(
+            0.0, -1.0, 0.0,
+            1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0,
+        ));
+        assert_close(&rodrigues(&Vector3::x(), 90.0), &Matrix3::new(
+            1.0, 0.0, 0.0,
+            0.0, 0.0, -1.0,
+            0.0, 1.0, 0.0,
+        ));
+        assert_close(&rodrigues(&Vector3::y(), 180.0), &Matrix3::new(
+            -1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            0.0, 0.0, -1.0,
+        ));
+    }
+
+    #[test]
+    fn rodrigues_normalizes_axis() {
+        // 绕 (1, 1, 1) 旋转 120 度把 x 轴轮换到 y 轴
+        let r = rodrigues(&Vector3::new(2.0, 2.0, 2.0), 120.0);
+        assert_close(&r, &Matrix3::new(
+            0.0, 0.0, 1.0,
+            1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+        ));
+    }
+
+    #[test]
+    fn zero_rotation_is_rejected() {
+        assert!(Transform::new().rotate(Vector3::zeros(), 30.0).is_err());
+        assert!(Transform::new().rotate_quaternion(0.0, 0.0, 0.0, 0.0).is_err());
+        assert!("r=0,0,0,30".parse::<Transform>().is_err());
+        assert!("q=0,0,0,0".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn parse_applies_items_in_order() {
+        let parsed: Transform = "s=2.5; r=0,1,0,140; t=0,0,-1".parse().unwrap();
+        let built = Transform::new()
+            .uniform_scale(2.5)
+            .rotate_y(140.0)
+            .translate(Vector3::new(0.0, 0.0, -1.0));
+        assert!((parsed.matrix() - built.matrix()).abs().max() < 1e-12);
+    }
+
+    #[test]
+    fn parse_quaternion_and_euler() {
+        // 绕 z 轴 90 度, 四元数不必归一化
+        let s = std::f64::consts::FRAC_1_SQRT_2;
+        let q: Transform = format!("q={},0,0,{}", 2.0 * s, 2.0 * s).parse().unwrap();
+        let e: Transform = "e=0,0,90".parse().unwrap();
+        assert!((q.matrix() - e.matrix()).abs().max() < 1e-12);
+        assert!((e.matrix() - Transform::new().rotate_z(90.0).matrix()).abs().max() < 1e-12);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_items() {
+        for s in ["t=1,2", "s=1,2", "x=1,2,3", "t=1,a,3", "r=0,1,0", "t", "s=0", "s=1,1,0", "s=inf", "t=0,NaN,0"] {
+            assert!(s.parse::<Transform>().is_err(), "{}", s);
+        }
+        assert!("".parse::<Transform>().unwrap().matrix() == Matrix4::identity());
+    }
+}
@@ -1,9 +1,9 @@
-// from 2023 Lab 2 可以兼容覆盖所有Lab使用的函数
+//! from 2023 Lab 2 可以兼容覆盖所有Lab使用的函数
 
 #![allow(dead_code)]
 
-use std::process::exit;
 use nalgebra::{Vector2, Vector3, Vector4};
+use crate::error::{Error, Result};
 
 #[derive(Default, Clone, Debug)]
 pub struct Triangle {
@@ -11,10 +11,11 @@ pub struct Triangle {
     pub color: [Vector3<f64>; 3],
     pub tex_coords: [Vector2<f64>; 3],
     pub normal: [Vector3<f64>; 3],
+    pub material_id: usize,
 }
 
 impl Triangle {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         let v4: Vector4<f64> = Vector4::new(0.0, 0.0, 0.0,0.0);
         let v3: Vector3<f64> = Vector3::new(0.0, 0.0, 0.0);
         Triangle {
@@ -22,6 +23,7 @@ impl Triangle {
             color: [v3; 3],
             tex_coords: [Vector2::new(0.0, 0.0); 3],
             normal: [v3; 3],
+            material_id: 0,
         }
     }
     pub fn set_vertex(&mut self, ind: usize, ver: Vector4<f64>) {
@@ -30,12 +32,13 @@ impl Triangle {
     pub fn set_normal(&mut self, ind: usize, n: Vector3<f64>) {
         self.normal[ind] = n;
     }
-    pub fn set_color(&mut self, ind: usize, r: f64, g: f64, b: f64) {
-        if r < 0.0 || r > 255.0 || g < 0.0 || g > 255.0 || b < 0.0 || b > 255.0 {
-            eprintln!("ERROR! Invalid color values");
-            exit(-1);
+    /// 颜色分量应在 0~255 之间
+    pub fn set_color(&mut self, ind: usize, r: f64, g: f64, b: f64) -> Result<()> {
+        if [r, g, b].iter().any(|c| !(0.0..=255.0).contains(c)) {
+            return Err(Error::InvalidParameter(format!("颜色 ({}, {}, {}) 超出 0~255 范围", r, g, b)));
         }
         self.color[ind] = Vector3::new(r / 255.0, g / 255.0, b / 255.0);
+        Ok(())
     }
     pub fn set_tex_coord(&mut self, ind: usize, s: f64, t: f64) {
         self.tex_coords[ind] = Vector2::new(s, t);
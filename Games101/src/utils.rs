@@ -1,8 +1,9 @@
+//! 公共类型与工具函数: 各变换矩阵、obj 加载、顶点/片元着色器、shader 选择
 #![allow(warnings)]
-use std::os::raw::c_void;
 use nalgebra::{Matrix3, Matrix4, Vector3, Vector4};
-use opencv::core::{Mat, MatTraitConst};
-use opencv::imgproc::{COLOR_RGB2BGR, cvt_color};
+use crate::color::{srgb_to_linear_v3, ColorSpace};
+use crate::error::{Error, Result};
+use crate::image_io;
 use crate::shader::{FragmentShaderPayload, VertexShaderPayload};
 use crate::texture::Texture;
 use crate::triangle::Triangle;
@@ -10,60 +11,25 @@ use crate::triangle::Triangle;
 pub type V3f = Vector3<f64>;
 pub type M4f = Matrix4<f64>;
 
-pub(crate) fn get_view_matrix(eye_pos: V3f) -> M4f {
-    let mut view: Matrix4<f64> = Matrix4::identity();
-    /*  implement your code here  */
-
-    view
-}
-
-pub(crate) fn get_model_matrix(rotation_angle: f64,scale: f64) -> M4f {
-    let mut model: Matrix4<f64> = Matrix4::identity();
-    /*  implement your code here  */
-
-    model
+/// frame buffer 中为线性颜色, 输出前应先经过 OutputTransform::apply 编码
+#[cfg(feature = "opencv")]
+pub fn frame_buffer2cv_mat(frame_buffer: &Vec<V3f>, width: usize, height: usize) -> Result<opencv::core::Mat> {
+    image_io::to_mat(&image_io::encode(frame_buffer, width, height))
 }
 
-pub(crate) fn get_model_matrix_lab3(rotation_angle: f64) -> M4f {
-    let mut model: M4f = Matrix4::identity();
-    let rad = rotation_angle.to_radians();
-    model[(0, 0)] = rad.cos();
-    model[(2, 2)] = model[(0, 0)];
-    model[(0, 2)] = rad.sin();
-    model[(2, 0)] = -model[(0, 2)];
-    let mut scale: M4f = Matrix4::identity();
-    scale[(0, 0)] = 2.5;
-    scale[(1, 1)] = 2.5;
-    scale[(2, 2)] = 2.5;
-    model * scale
-}
-
-pub(crate) fn get_projection_matrix(eye_fov: f64, aspect_ratio: f64, z_near: f64, z_far: f64) -> M4f {
-    let mut projection: Matrix4<f64> = Matrix4::identity();
-    let mut scale: M4f = Matrix4::identity();
-    /*  implement your code here  */
-
-    projection * scale
-}
-
-pub(crate) fn frame_buffer2cv_mat(frame_buffer: &Vec<V3f>) -> Mat {
-    let mut image = unsafe {
-        Mat::new_rows_cols_with_data(
-            700, 700,
-            opencv::core::CV_64FC3,
-            frame_buffer.as_ptr() as *mut c_void,
-            opencv::core::Mat_AUTO_STEP,
-        ).unwrap()
-    };
-    let mut img = Mat::copy(&image).unwrap();
-    image.convert_to(&mut img, opencv::core::CV_8UC3, 1.0, 1.0).expect("panic message");
-    cvt_color(&img, &mut image, COLOR_RGB2BGR, 0).unwrap();
-    image
-}
-
-pub fn load_triangles(obj_file: &str) -> Vec<Triangle> {
-    let (models, _) = tobj::load_obj(&obj_file, &tobj::LoadOptions::default()).unwrap();
-    let mesh = &models[0].mesh;
+/// 按 obj 中的面顺序读取三角形; 需要包围体与分簇时使用 Mesh::load
+pub fn load_triangles(obj_file: &str) -> Result<Vec<Triangle>> {
+    let (models, _) = tobj::load_obj(&obj_file, &tobj::LoadOptions::default()).map_err(|e| match e {
+        tobj::LoadError::OpenFileFailed => Error::io(obj_file, std::io::Error::from(std::io::ErrorKind::NotFound)),
+        e => Error::decode(obj_file, e),
+    })?;
+    let mesh = &models.first()
+        .ok_or_else(|| Error::InvalidGeometry(format!("{} 中没有网格", obj_file)))?
+        .mesh;
+    let vertices = mesh.positions.len() / 3;
+    if mesh.normals.len() < vertices * 3 || mesh.texcoords.len() < vertices * 2 {
+        return Err(Error::InvalidGeometry(format!("{} 的每个顶点都需要法线与纹理坐标", obj_file)));
+    }
     let n = mesh.indices.len() / 3;
     let mut triangles = vec![Triangle::default(); n];
 
@@ -81,13 +47,20 @@ pub fn load_triangles(obj_file: &str) -> Vec<Triangle> {
             let tex = &mesh.texcoords[2 * idx[j]..2 * idx[j] + 2];
             triangles[vtx].set_tex_coord(j, tex[0] as f64, tex[1] as f64);
         }
+        triangles[vtx].material_id = mesh.material_id.unwrap_or(0);
     }
-    triangles
+    Ok(triangles)
 }
 
-// 选择对应的Shader
+/// choose_shader_texture 可识别的着色器
+pub const SHADERS: [&str; 5] = ["normal", "phong", "texture", "bump", "displacement"];
+
+/// 选择对应的Shader, 并加载它需要的纹理: texture 为颜色贴图, bump / displacement 为高度图;
+/// texture_file 为 None 时使用 obj_path 下 spot 模型自带的贴图
 pub fn choose_shader_texture(method: &str,
-                             obj_path: &str) -> (fn(&FragmentShaderPayload) -> Vector3<f64>, Option<Texture>) {
+                             obj_path: &str,
+                             texture_file: Option<&str>) -> Result<(fn(&FragmentShaderPayload) -> Vector3<f64>, Option<Texture>)> {
+    let file = |default: &str| texture_file.map_or(obj_path.to_owned() + default, String::from);
     let mut active_shader: fn(&FragmentShaderPayload) -> Vector3<f64> = phong_fragment_shader;
     let mut tex = None;
     if method == "normal" {
@@ -96,53 +69,42 @@ pub fn choose_shader_texture(method: &str,
     } else if method == "texture" {
         println!("Rasterizing using the normal shader");
         active_shader = texture_fragment_shader;
-        tex = Some(Texture::new(&(obj_path.to_owned() + "spot_texture.png")));
+        tex = Some(Texture::new(&file("spot_texture.png"))?);
     } else if method == "phong" {
         println!("Rasterizing using the phong shader");
         active_shader = phong_fragment_shader;
     } else if method == "bump" {
         println!("Rasterizing using the bump shader");
         active_shader = bump_fragment_shader;
+        tex = Some(Texture::with_color_space(&file("hmap.jpg"), ColorSpace::Linear)?); // 高度图为线性数据
     } else if method == "displacement" {
         println!("Rasterizing using the displacement shader");
         active_shader = displacement_fragment_shader;
+        tex = Some(Texture::with_color_space(&file("hmap.jpg"), ColorSpace::Linear)?);
     }
-    (active_shader, tex)
+    Ok((active_shader, tex))
 }
 
 pub fn vertex_shader(payload: &VertexShaderPayload) -> V3f {
     payload.position
 }
 
-#[derive(Default)]
-struct Light {
-    pub position: V3f,
-    pub intensity: V3f,
-}
-
 pub fn normal_fragment_shader(payload: &FragmentShaderPayload) -> V3f {
     let result_color =
         (payload.normal.xyz().normalize() + Vector3::new(1.0, 1.0, 1.0)) / 2.0;
-    result_color * 255.0
+    // 法线可视化不参与光照, 预先转到线性空间, 输出编码后保持原样
+    srgb_to_linear_v3(&result_color) * 255.0
 }
 
 pub fn phong_fragment_shader(payload: &FragmentShaderPayload) -> V3f {
     // 泛光、漫反射、高光系数
     let ka = Vector3::new(0.005, 0.005, 0.005);
-    let kd = payload.color;
+    let kd = srgb_to_linear_v3(&payload.color).component_mul(&payload.tint); // 顶点颜色为 sRGB, 光照在线性空间中计算
     let ks = Vector3::new(0.7937, 0.7937, 0.7937);
 
-    // 灯光位置和强度
-    let l1 = Light {
-        position: Vector3::new(20.0, 20.0, 20.0),
-        intensity: Vector3::new(500.0, 500.0, 500.0),
-    };
-    let l2 = Light {
-        position: Vector3::new(-20.0, 20.0, 0.0),
-        intensity: Vector3::new(500.0, 500.0, 500.0),
-    };
-    let lights = vec![l1, l2];
-    let amb_light_intensity = Vector3::new(10.0, 10.0, 10.0);
+    // 灯光位置和强度 (已考虑阴影遮挡)
+    let lights = payload.lights();
+    let amb_light_intensity = Vector3::new(10.0, 10.0, 10.0) * payload.ambient_occlusion; // 经 SSAO 调制
     let eye_pos = Vector3::new(0.0, 0.0, 10.0);
 
     let p = 150.0;
@@ -173,19 +135,11 @@ pub fn texture_fragment_shader(payload: &FragmentShaderPayload) -> V3f {
         None => Vector3::new(0.0, 0.0, 0.0),
         Some(texture) => Vector3::new(0.0, 0.0, 0.0), // Do modification here
     };
-    let kd = texture_color / 255.0; // 材质颜色影响漫反射系数
+    let kd = (texture_color / 255.0).component_mul(&payload.tint); // 材质颜色影响漫反射系数 (get_color 已返回线性颜色)
     let ks = Vector3::new(0.7937, 0.7937, 0.7937);
 
-    let l1 = Light {
-        position: Vector3::new(20.0, 20.0, 20.0),
-        intensity: Vector3::new(500.0, 500.0, 500.0),
-    };
-    let l2 = Light {
-        position: Vector3::new(-20.0, 20.0, 0.0),
-        intensity: Vector3::new(500.0, 500.0, 500.0),
-    };
-    let lights = vec![l1, l2];
-    let amb_light_intensity = Vector3::new(10.0, 10.0, 10.0);
+    let lights = payload.lights();
+    let amb_light_intensity = Vector3::new(10.0, 10.0, 10.0) * payload.ambient_occlusion;
     let eye_pos = Vector3::new(0.0, 0.0, 10.0);
 
     let p = 150.0;
@@ -207,19 +161,11 @@ pub fn texture_fragment_shader(payload: &FragmentShaderPayload) -> V3f {
 
 pub fn bump_fragment_shader(payload: &FragmentShaderPayload) -> V3f {
     let ka = Vector3::new(0.005, 0.005, 0.005);
-    let kd = payload.color;
+    let kd = srgb_to_linear_v3(&payload.color).component_mul(&payload.tint);
     let ks = Vector3::new(0.7937, 0.7937, 0.7937);
 
-    let l1 = Light {
-        position: Vector3::new(20.0, 20.0, 20.0),
-        intensity: Vector3::new(500.0, 500.0, 500.0),
-    };
-    let l2 = Light {
-        position: Vector3::new(-20.0, 20.0, 0.0),
-        intensity: Vector3::new(500.0, 500.0, 500.0),
-    };
-    let lights = vec![l1, l2];
-    let amb_light_intensity = Vector3::new(10.0, 10.0, 10.0);
+    let lights = payload.lights();
+    let amb_light_intensity = Vector3::new(10.0, 10.0, 10.0) * payload.ambient_occlusion;
     let eye_pos = Vector3::new(0.0, 0.0, 10.0);
 
     let p = 150.0;
@@ -248,19 +194,11 @@ pub fn bump_fragment_shader(payload: &FragmentShaderPayload) -> V3f {
 
 pub fn displacement_fragment_shader(payload: &FragmentShaderPayload) -> V3f {
     let ka = Vector3::new(0.005, 0.005, 0.005);
-    let kd = payload.color;
+    let kd = srgb_to_linear_v3(&payload.color).component_mul(&payload.tint);
     let ks = Vector3::new(0.7937, 0.7937, 0.7937);
 
-    let l1 = Light {
-        position: Vector3::new(20.0, 20.0, 20.0),
-        intensity: Vector3::new(500.0, 500.0, 500.0),
-    };
-    let l2 = Light {
-        position: Vector3::new(-20.0, 20.0, 0.0),
-        intensity: Vector3::new(500.0, 500.0, 500.0),
-    };
-    let lights = vec![l1, l2];
-    let amb_light_intensity = Vector3::new(10.0, 10.0, 10.0);
+    let lights = payload.lights();
+    let amb_light_intensity = Vector3::new(10.0, 10.0, 10.0) * payload.ambient_occlusion;
     let eye_pos = Vector3::new(0.0, 0.0, 10.0);
 
     let p = 150.0;
@@ -0,0 +1,67 @@
+//! task3 的交互式查看器: 与 mesh 命令使用相同的设置, 每帧通过 rasterizer3 重新绘制, 可实时切换 shader
+//!   a/d: 旋转模型          1~5: normal / phong / texture / bump / displacement
+//!   f: 线框  c: 背面剔除    p: 截图          其余相机按键见 Camera::handle_key
+//!   ESC: 退出
+
+use std::time::Instant;
+use opencv::core::{Point, Scalar};
+use opencv::highgui::{imshow, wait_key};
+use opencv::imgproc::{put_text, FONT_HERSHEY_SIMPLEX, LINE_8};
+use crate::error::Result;
+use crate::image_io;
+use crate::rasterizer3::Buffer;
+use crate::task3::{self, T3Options};
+use crate::utils::*;
+
+pub fn view(method: String, opts: T3Options) -> Result<()> {
+    println!("选择任务3 (交互模式)");
+    let (mut r, mesh, mut camera, base, mut output) = task3::setup(&method, &opts)?;
+    let (width, height) = (opts.view.width, opts.view.height);
+    let mut method = method;
+    let (mut angle, mut wireframe, mut culling) = (0.0, false, false);
+    let mut screenshot = 0;
+
+    let mut k = 0;
+    while k != 27 {
+        let start = Instant::now();
+        r.set_wireframe(wireframe);
+        r.set_backface_culling(culling);
+        r.clear(Buffer::Both);
+        r.set_view(camera.view_matrix());
+        r.set_projection(camera.projection_matrix());
+        task3::draw(&mut r, &mesh, &base.clone().rotate_y(angle), &opts)?;
+        let frame_time = start.elapsed().as_secs_f64() * 1000.0;
+
+        let frame = output.apply(r.frame_buffer(), width);
+        let mut image = frame_buffer2cv_mat(&frame, width, height)?;
+        let overlay = format!("{:.1} ms  {}{}{}", frame_time, method,
+                              if wireframe { "  wireframe" } else { "" },
+                              if culling { "  culling" } else { "" });
+        put_text(&mut image, &overlay, Point::new(10, 25), FONT_HERSHEY_SIMPLEX, 0.6,
+                 Scalar::new(255.0, 255.0, 255.0, 0.0), 1, LINE_8, false)?;
+        imshow("task3", &image)?;
+
+        k = wait_key(1)?;
+        match u8::try_from(k).map(|k| k as char) {
+            Ok('a') => angle += 10.0,
+            Ok('d') => angle -= 10.0,
+            Ok('f') => wireframe = !wireframe,
+            Ok('c') => culling = !culling,
+            Ok('p') => {
+                // 截图不带左上角的信息
+                let name = format!("screenshot_{:04}.png", screenshot);
+                image_io::save_frame(&name, &frame, width, height)?;
+                println!("saved {}", name);
+                screenshot += 1;
+            }
+            Ok(c @ '1'..='5') => {
+                method = SHADERS[c as usize - '1' as usize].to_owned();
+                output = task3::set_method(&mut r, &method, &opts)?;
+            }
+            _ => {
+                camera.handle_key(k);
+            }
+        }
+    }
+    Ok(())
+}